@@ -0,0 +1,65 @@
+//! Times `jegui::Core::run` (layout, tessellation, buffer uploads and draw submission) for a UI
+//! of several separately clipped windows, rendered headlessly. On a software adapter this
+//! includes rasterizing the frame. Run with `cargo bench --bench egui`.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use render_engine::jwgpu;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+use common::UiApp;
+
+const WARMUP_FRAMES:u32 = 20;
+const FRAMES:u32 = 200;
+
+/// Times the rendering of the wrapped app after the warmup frames.
+struct BenchApp<A>{
+    app:A,
+    frame:u32,
+    ui_time:Rc<Cell<Duration>>,
+}
+
+impl<A:jwgpu::App> jwgpu::App for BenchApp<A>{
+    fn render(&mut self, jwgpu_core:&mut jwgpu::Core)->Result<(), jwgpu::Error>{
+        let start = Instant::now();
+        self.app.render(jwgpu_core)?;
+        self.frame += 1;
+        if self.frame>WARMUP_FRAMES{
+            self.ui_time.set(self.ui_time.get() + start.elapsed());
+        }
+        Ok(())
+    }
+}
+
+/// Several separately clipped windows over a background panel.
+fn ui(ctx:&egui::Context){
+    egui::CentralPanel::default().show(ctx, |ui| ui.label("background"));
+    for window in 0..6{
+        egui::Window::new(format!("Window {}", window))
+            .default_pos(egui::pos2(20.0 + 150.0*(window%3) as f32, 20.0 + 280.0*(window/3) as f32))
+            .default_size(egui::vec2(140.0, 240.0))
+            .show(ctx, |ui| {
+                for row in 0..10{
+                    ui.horizontal(|ui| {
+                        ui.label(format!("row {}", row));
+                        let _ = ui.button("button");
+                        ui.checkbox(&mut (row%2==0), "check");
+                    });
+                }
+            });
+    }
+}
+
+fn main(){
+    let start = Instant::now();
+    let ui_time = Rc::new(Cell::new(Duration::ZERO));
+    let images = jwgpu::run_headless(jwgpu::RunConfig::new(480.0, 600.0), WARMUP_FRAMES + FRAMES, |jwgpu_core| {
+        Ok(BenchApp{app:UiApp::new(jwgpu_core, ui)?, frame:0, ui_time:ui_time.clone()})
+    }).unwrap();
+    assert_eq!(images.len() as u32, WARMUP_FRAMES + FRAMES);
+    println!("egui: {:.3} ms per frame over {} frames ({:.1} s in total including readback)",
+        ui_time.get().as_secs_f64()*1000.0/FRAMES as f64, FRAMES, start.elapsed().as_secs_f64());
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+use crate::jinput::{Input, MouseButton};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Button{
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Button{
+    pub fn is_down(&self, input:&Input)->bool{
+        match *self{
+            Button::Key(key)=>input.is_key_down(key),
+            Button::Mouse(button)=>input.is_mouse_down(button),
+        }
+    }
+
+    pub fn is_pressed(&self, input:&Input)->bool{
+        match *self{
+            Button::Key(key)=>input.is_key_pressed(key),
+            Button::Mouse(button)=>input.is_mouse_pressed(button),
+        }
+    }
+
+    pub fn is_released(&self, input:&Input)->bool{
+        match *self{
+            Button::Key(key)=>input.is_key_released(key),
+            Button::Mouse(button)=>input.is_mouse_released(button),
+        }
+    }
+}
+
+/// A button plus the modifiers that must be held with it, e.g. Ctrl+S. The modifiers must match
+/// exactly, so Ctrl+S does not fire on Ctrl+Shift+S and a plain S does not fire on Ctrl+S.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding{
+    pub button:Button,
+    #[serde(default)]
+    pub modifiers:ModifiersState,
+}
+
+impl Binding{
+    pub fn key(key:KeyCode)->Self{
+        Self{button:Button::Key(key), modifiers:ModifiersState::empty()}
+    }
+
+    pub fn mouse(button:MouseButton)->Self{
+        Self{button:Button::Mouse(button), modifiers:ModifiersState::empty()}
+    }
+
+    pub fn with_modifiers(mut self, modifiers:ModifiersState)->Self{
+        self.modifiers = modifiers;
+        self
+    }
+
+    fn modifiers_held(&self, input:&Input)->bool{
+        input.modifiers()==self.modifiers
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Axis{
+    X,
+    Y,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding{
+    /// -1 while `negative` is held, +1 while `positive` is held.
+    Buttons{negative:Button, positive:Button},
+    /// Cursor movement this frame in physical pixels, times `scale`.
+    MouseMotion{axis:Axis, scale:f32},
+    /// Scroll this frame in lines, times `scale`.
+    Scroll{axis:Axis, scale:f32},
+}
+
+impl AxisBinding{
+    fn value(&self, input:&Input)->f32{
+        let pick = |v:cgmath::Vector2<f32>, axis:Axis| match axis{
+            Axis::X=>v.x,
+            Axis::Y=>v.y,
+        };
+        match *self{
+            AxisBinding::Buttons{negative, positive}=>{
+                positive.is_down(input) as i32 as f32 - negative.is_down(input) as i32 as f32
+            }
+            AxisBinding::MouseMotion{axis, scale}=>pick(input.mouse_delta(), axis)*scale,
+            AxisBinding::Scroll{axis, scale}=>pick(input.scroll_delta(), axis)*scale,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ActionMapError{
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl std::fmt::Display for ActionMapError{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            ActionMapError::Io(err)=>write!(f, "failed to access binding file: {}", err),
+            ActionMapError::Parse(err)=>write!(f, "failed to parse bindings: {}", err),
+            ActionMapError::Serialize(err)=>write!(f, "failed to serialize bindings: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ActionMapError{}
+
+/// Named actions and axes, each with any number of bindings. Stored as RON.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap{
+    #[serde(default)]
+    pub actions:BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    pub axes:BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl ActionMap{
+    pub fn bind(&mut self, action:&str, binding:Binding){
+        self.actions.entry(action.to_owned()).or_default().push(binding);
+    }
+
+    pub fn bind_axis(&mut self, axis:&str, binding:AxisBinding){
+        self.axes.entry(axis.to_owned()).or_default().push(binding);
+    }
+
+    /// Replaces all bindings of `action`.
+    pub fn rebind(&mut self, action:&str, bindings:Vec<Binding>){
+        self.actions.insert(action.to_owned(), bindings);
+    }
+
+    pub fn rebind_axis(&mut self, axis:&str, bindings:Vec<AxisBinding>){
+        self.axes.insert(axis.to_owned(), bindings);
+    }
+
+    fn bindings(&self, action:&str)->impl Iterator<Item = &Binding>{
+        self.actions.get(action).into_iter().flatten()
+    }
+
+    pub fn is_down(&self, input:&Input, action:&str)->bool{
+        self.bindings(action).any(|binding| binding.button.is_down(input) && binding.modifiers_held(input))
+    }
+
+    /// A binding of `action` was triggered this frame.
+    pub fn is_pressed(&self, input:&Input, action:&str)->bool{
+        self.bindings(action).any(|binding| binding.button.is_pressed(input) && binding.modifiers_held(input))
+    }
+
+    pub fn is_released(&self, input:&Input, action:&str)->bool{
+        self.bindings(action).any(|binding| binding.button.is_released(input) && binding.modifiers_held(input))
+    }
+
+    /// Sum of all bindings of `axis`; button pairs are clamped to -1..=1 before mouse input is added.
+    pub fn axis(&self, input:&Input, axis:&str)->f32{
+        let mut buttons = 0.0;
+        let mut analog = 0.0;
+        for binding in self.axes.get(axis).into_iter().flatten(){
+            match binding{
+                AxisBinding::Buttons{..}=>buttons += binding.value(input),
+                _=>analog += binding.value(input),
+            }
+        }
+        f32::clamp(buttons, -1.0, 1.0) + analog
+    }
+
+    pub fn from_ron(text:&str)->Result<Self, ActionMapError>{
+        ron::from_str(text).map_err(ActionMapError::Parse)
+    }
+
+    pub fn to_ron(&self)->Result<String, ActionMapError>{
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(ActionMapError::Serialize)
+    }
+
+    pub fn load(path:impl AsRef<Path>)->Result<Self, ActionMapError>{
+        Self::from_ron(&std::fs::read_to_string(path).map_err(ActionMapError::Io)?)
+    }
+
+    pub fn save(&self, path:impl AsRef<Path>)->Result<(), ActionMapError>{
+        std::fs::write(path, self.to_ron()?).map_err(ActionMapError::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::jinput::{ElementState, Event, PhysicalKey};
+
+    fn key(input:&mut Input, key:KeyCode, state:ElementState){
+        input.handle_event(&Event::KeyboardInput{physical_key:PhysicalKey::Code(key), text:None, state, repeat:false});
+    }
+
+    fn save_map()->ActionMap{
+        let mut map = ActionMap::default();
+        map.bind("save", Binding::key(KeyCode::KeyS).with_modifiers(ModifiersState::CONTROL));
+        map.bind("step", Binding::key(KeyCode::KeyS));
+        map
+    }
+
+    #[test]
+    fn modifiers_must_match_exactly(){
+        let map = save_map();
+        let mut input = Input::default();
+        input.handle_event(&Event::ModifiersChanged(ModifiersState::CONTROL));
+        key(&mut input, KeyCode::KeyS, ElementState::Pressed);
+        assert!(map.is_pressed(&input, "save"));
+        assert!(map.is_down(&input, "save"));
+        assert!(!map.is_pressed(&input, "step"));
+
+        input.end_frame();
+        input.handle_event(&Event::ModifiersChanged(ModifiersState::CONTROL | ModifiersState::SHIFT));
+        assert!(!map.is_down(&input, "save"));
+
+        input.handle_event(&Event::ModifiersChanged(ModifiersState::empty()));
+        assert!(map.is_down(&input, "step"));
+        assert!(!map.is_pressed(&input, "step"));
+    }
+
+    #[test]
+    fn released_respects_modifiers(){
+        let map = save_map();
+        let mut input = Input::default();
+        key(&mut input, KeyCode::KeyS, ElementState::Pressed);
+        input.end_frame();
+        key(&mut input, KeyCode::KeyS, ElementState::Released);
+        assert!(map.is_released(&input, "step"));
+        assert!(!map.is_released(&input, "save"));
+
+        input.end_frame();
+        input.handle_event(&Event::ModifiersChanged(ModifiersState::CONTROL));
+        key(&mut input, KeyCode::KeyS, ElementState::Pressed);
+        input.end_frame();
+        key(&mut input, KeyCode::KeyS, ElementState::Released);
+        assert!(map.is_released(&input, "save"));
+        assert!(!map.is_released(&input, "step"));
+    }
+
+    #[test]
+    fn mouse_bindings(){
+        let mut map = ActionMap::default();
+        map.bind("fire", Binding::mouse(MouseButton::Left));
+        let mut input = Input::default();
+        input.handle_event(&Event::MouseInput{button:MouseButton::Left, state:ElementState::Pressed});
+        assert!(map.is_pressed(&input, "fire"));
+        assert!(!map.is_pressed(&input, "unbound"));
+    }
+
+    #[test]
+    fn axes_combine_buttons_and_analog_input(){
+        let mut map = ActionMap::default();
+        let buttons = AxisBinding::Buttons{negative:Button::Key(KeyCode::ArrowLeft), positive:Button::Key(KeyCode::ArrowRight)};
+        map.bind_axis("x", buttons);
+        map.bind_axis("x", AxisBinding::Buttons{negative:Button::Key(KeyCode::KeyA), positive:Button::Key(KeyCode::KeyD)});
+        map.bind_axis("x", AxisBinding::MouseMotion{axis:Axis::X, scale:0.5});
+        map.bind_axis("zoom", AxisBinding::Scroll{axis:Axis::Y, scale:2.0});
+
+        let mut input = Input::default();
+        assert_eq!(map.axis(&input, "x"), 0.0);
+        key(&mut input, KeyCode::ArrowRight, ElementState::Pressed);
+        key(&mut input, KeyCode::KeyD, ElementState::Pressed);
+        assert_eq!(map.axis(&input, "x"), 1.0);
+        input.handle_event(&Event::CursorMoved{x:10.0, y:0.0});
+        input.handle_event(&Event::CursorMoved{x:14.0, y:0.0});
+        assert_eq!(map.axis(&input, "x"), 3.0);
+        key(&mut input, KeyCode::ArrowLeft, ElementState::Pressed);
+        key(&mut input, KeyCode::KeyA, ElementState::Pressed);
+        assert_eq!(map.axis(&input, "x"), 2.0);
+
+        input.handle_event(&Event::MouseWheel(crate::jinput::MouseScrollDelta::LineDelta(0.0, -1.5)));
+        assert_eq!(map.axis(&input, "zoom"), -3.0);
+        input.end_frame();
+        assert_eq!(map.axis(&input, "zoom"), 0.0);
+    }
+
+    #[test]
+    fn ron_round_trip(){
+        let mut map = save_map();
+        map.bind("fire", Binding::mouse(MouseButton::Left));
+        map.bind_axis("x", AxisBinding::Buttons{negative:Button::Key(KeyCode::ArrowLeft), positive:Button::Key(KeyCode::ArrowRight)});
+        map.bind_axis("zoom", AxisBinding::Scroll{axis:Axis::Y, scale:2.0});
+        let text = map.to_ron().unwrap();
+        assert_eq!(ActionMap::from_ron(&text).unwrap(), map);
+    }
+
+    #[test]
+    fn ron_modifiers_default_to_none(){
+        let map = ActionMap::from_ron("(actions:{\"jump\":[(button:Key(Space))]})").unwrap();
+        assert_eq!(map.actions["jump"], [Binding::key(KeyCode::Space)]);
+        assert!(map.axes.is_empty());
+        assert!(matches!(ActionMap::from_ron("(actions:{\"jump\":[(button:Key(NotAKey))]})"), Err(ActionMapError::Parse(_))));
+    }
+}
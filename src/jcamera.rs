@@ -0,0 +1,360 @@
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Vector2, Vector3};
+use winit::keyboard::KeyCode;
+
+use crate::jactions::Button;
+use crate::jinput::{Input, MouseButton};
+use crate::jwgpu;
+
+/// Pitch is kept inside this range so the view never flips over the vertical axis.
+const MAX_PITCH:f32 = 89.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection{
+    /// `fovy` is the vertical field of view.
+    Perspective{fovy:Deg<f32>, near:f32, far:f32},
+    /// `height` is the world-space height of the view; the width follows the aspect.
+    Orthographic{height:f32, near:f32, far:f32},
+}
+
+/// A camera placed at `position` looking along `yaw` and `pitch`. Yaw 0 looks down -Z and
+/// turns right as it grows; positive pitch looks up.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Camera{
+    pub position:Point3<f32>,
+    pub yaw:Deg<f32>,
+    pub pitch:Deg<f32>,
+    pub projection:Projection,
+    /// Size of the render target in physical pixels; see `resize`.
+    pub viewport:Vector2<f32>,
+}
+
+impl Camera{
+    pub fn perspective(position:Point3<f32>, fovy:Deg<f32>, near:f32, far:f32)->Self{
+        Self::new(position, Projection::Perspective{fovy, near, far})
+    }
+
+    pub fn orthographic(position:Point3<f32>, height:f32, near:f32, far:f32)->Self{
+        Self::new(position, Projection::Orthographic{height, near, far})
+    }
+
+    fn new(position:Point3<f32>, projection:Projection)->Self{
+        Self{position, yaw:Deg(0.0), pitch:Deg(0.0), projection, viewport:Vector2::new(1.0, 1.0)}
+    }
+
+    /// Call from `App::resized` so the aspect follows the window.
+    pub fn resize(&mut self, width:u32, height:u32){
+        self.viewport = Vector2::new(width.max(1) as f32, height.max(1) as f32);
+    }
+
+    pub fn aspect(&self)->f32{
+        self.viewport.x/self.viewport.y
+    }
+
+    pub fn forward(&self)->Vector3<f32>{
+        let (yaw_sin, yaw_cos) = cgmath::Rad::from(self.yaw).0.sin_cos();
+        let (pitch_sin, pitch_cos) = cgmath::Rad::from(self.pitch).0.sin_cos();
+        Vector3::new(yaw_sin*pitch_cos, pitch_sin, -yaw_cos*pitch_cos)
+    }
+
+    pub fn right(&self)->Vector3<f32>{
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
+
+    pub fn up(&self)->Vector3<f32>{
+        self.right().cross(self.forward())
+    }
+
+    pub fn view_matrix(&self)->Matrix4<f32>{
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    /// Maps view space to wgpu clip space, depth 0 at `near` and 1 at `far`.
+    pub fn projection_matrix(&self)->Matrix4<f32>{
+        let projection = match self.projection{
+            Projection::Perspective{fovy, near, far}=>cgmath::perspective(fovy, self.aspect(), near, far),
+            Projection::Orthographic{height, near, far}=>{
+                let half_height = height/2.0;
+                let half_width = half_height*self.aspect();
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        };
+        jwgpu::OPENGL_TO_WGPU_MATRIX * projection
+    }
+
+    /// World-space size of a pixel on a plane `distance` in front of the camera.
+    pub fn world_units_per_pixel(&self, distance:f32)->f32{
+        match self.projection{
+            Projection::Perspective{fovy, ..}=>2.0*distance*(cgmath::Rad::from(fovy).0/2.0).tan()/self.viewport.y,
+            Projection::Orthographic{height, ..}=>height/self.viewport.y,
+        }
+    }
+
+    pub fn uniform(&self)->jwgpu::CameraUniform{
+        jwgpu::CameraUniform::new(self.view_matrix(), self.projection_matrix())
+    }
+
+    fn add_rotation(&mut self, yaw:f32, pitch:f32){
+        self.yaw = Deg((self.yaw.0 + yaw).rem_euclid(360.0));
+        self.pitch = Deg((self.pitch.0 + pitch).clamp(-MAX_PITCH, MAX_PITCH));
+    }
+}
+
+/// Moves a `Camera` in response to input, once per frame.
+pub trait CameraController{
+    /// `dt` is the frame time in seconds.
+    fn update(&mut self, camera:&mut Camera, input:&Input, dt:f32);
+}
+
+/// Circles `target` at `distance`: dragging with `rotate_button` turns the camera, scrolling
+/// moves it closer or further away.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitController{
+    pub target:Point3<f32>,
+    pub distance:f32,
+    pub min_distance:f32,
+    pub max_distance:f32,
+    pub rotate_button:Button,
+    /// Degrees per pixel dragged.
+    pub rotate_speed:f32,
+    /// Fraction of the distance covered per scrolled line.
+    pub zoom_speed:f32,
+}
+
+impl OrbitController{
+    pub fn new(target:Point3<f32>, distance:f32)->Self{
+        Self{
+            target,
+            distance,
+            min_distance:1.0,
+            max_distance:f32::MAX,
+            rotate_button:Button::Mouse(MouseButton::Left),
+            rotate_speed:0.3,
+            zoom_speed:0.1,
+        }
+    }
+}
+
+impl CameraController for OrbitController{
+    fn update(&mut self, camera:&mut Camera, input:&Input, _dt:f32){
+        if self.rotate_button.is_down(input){
+            let delta = input.mouse_delta()*self.rotate_speed;
+            camera.add_rotation(delta.x, -delta.y);
+        }
+        self.distance = (self.distance*(1.0 - self.zoom_speed).powf(input.scroll_delta().y))
+            .clamp(self.min_distance, self.max_distance);
+        camera.position = self.target - camera.forward()*self.distance;
+    }
+}
+
+/// Free flight: the movement buttons move along the view direction and the world's vertical
+/// axis, dragging with `look_button` turns the camera.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlyController{
+    pub forward:Button,
+    pub back:Button,
+    pub left:Button,
+    pub right:Button,
+    pub up:Button,
+    pub down:Button,
+    pub look_button:Button,
+    /// World units per second.
+    pub speed:f32,
+    /// Degrees per pixel dragged.
+    pub look_speed:f32,
+}
+
+impl FlyController{
+    /// WASD to move, E and Q to rise and sink, right mouse button to look around.
+    pub fn new(speed:f32)->Self{
+        Self{
+            forward:Button::Key(KeyCode::KeyW),
+            back:Button::Key(KeyCode::KeyS),
+            left:Button::Key(KeyCode::KeyA),
+            right:Button::Key(KeyCode::KeyD),
+            up:Button::Key(KeyCode::KeyE),
+            down:Button::Key(KeyCode::KeyQ),
+            look_button:Button::Mouse(MouseButton::Right),
+            speed,
+            look_speed:0.2,
+        }
+    }
+}
+
+impl CameraController for FlyController{
+    fn update(&mut self, camera:&mut Camera, input:&Input, dt:f32){
+        if self.look_button.is_down(input){
+            let delta = input.mouse_delta()*self.look_speed;
+            camera.add_rotation(delta.x, -delta.y);
+        }
+        let axis = |negative:Button, positive:Button| positive.is_down(input) as i32 as f32 - negative.is_down(input) as i32 as f32;
+        let direction = camera.forward()*axis(self.back, self.forward)
+            + camera.right()*axis(self.left, self.right)
+            + Vector3::unit_y()*axis(self.down, self.up);
+        if direction.magnitude2()>0.0{
+            camera.position += direction.normalize()*self.speed*dt;
+        }
+    }
+}
+
+/// 2D-style navigation: dragging with `pan_button` moves the view so the point under the
+/// cursor follows it, scrolling zooms. Orthographic cameras zoom by shrinking their height,
+/// perspective ones by moving towards the plane `distance` ahead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PanZoomController{
+    pub pan_button:Button,
+    /// Distance of the plane that follows the cursor, for perspective cameras.
+    pub distance:f32,
+    /// Fraction of the view covered per scrolled line.
+    pub zoom_speed:f32,
+    /// Smallest orthographic height or perspective distance.
+    pub min_zoom:f32,
+    /// Largest orthographic height or perspective distance.
+    pub max_zoom:f32,
+}
+
+impl PanZoomController{
+    pub fn new(distance:f32)->Self{
+        Self{
+            pan_button:Button::Mouse(MouseButton::Middle),
+            distance,
+            zoom_speed:0.1,
+            min_zoom:0.01,
+            max_zoom:f32::MAX,
+        }
+    }
+}
+
+impl CameraController for PanZoomController{
+    fn update(&mut self, camera:&mut Camera, input:&Input, _dt:f32){
+        if self.pan_button.is_down(input){
+            let delta = input.mouse_delta()*camera.world_units_per_pixel(self.distance);
+            camera.position += camera.up()*delta.y - camera.right()*delta.x;
+        }
+        let scale = (1.0 - self.zoom_speed).powf(input.scroll_delta().y);
+        match &mut camera.projection{
+            Projection::Orthographic{height, ..}=>*height = (*height*scale).clamp(self.min_zoom, self.max_zoom),
+            Projection::Perspective{..}=>{
+                let distance = (self.distance*scale).clamp(self.min_zoom, self.max_zoom);
+                camera.position += camera.forward()*(self.distance - distance);
+                self.distance = distance;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use cgmath::EuclideanSpace;
+    use crate::jinput::{ElementState, Event, MouseScrollDelta, PhysicalKey};
+
+    fn assert_near(actual:Vector3<f32>, expected:Vector3<f32>){
+        assert!((actual - expected).magnitude()<1e-4, "{:?} is not {:?}", actual, expected);
+    }
+
+    /// `point` in normalized device coordinates.
+    fn ndc(camera:&Camera, point:Point3<f32>)->Vector3<f32>{
+        let clip = camera.projection_matrix()*camera.view_matrix()*point.to_homogeneous();
+        clip.truncate()/clip.w
+    }
+
+    /// Drags the mouse by `dx`, `dy` pixels with `button` held.
+    fn drag(input:&mut Input, button:MouseButton, dx:f64, dy:f64){
+        input.handle_event(&Event::MouseInput{button, state:ElementState::Pressed});
+        input.handle_event(&Event::CursorMoved{x:0.0, y:0.0});
+        input.handle_event(&Event::CursorMoved{x:dx, y:dy});
+    }
+
+    fn press(input:&mut Input, key:KeyCode){
+        input.handle_event(&Event::KeyboardInput{physical_key:PhysicalKey::Code(key), text:None, state:ElementState::Pressed, repeat:false});
+    }
+
+    #[test]
+    fn axes_follow_yaw(){
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 0.0), Deg(60.0), 0.1, 100.0);
+        assert_near(camera.forward(), -Vector3::unit_z());
+        assert_near(camera.right(), Vector3::unit_x());
+        assert_near(camera.up(), Vector3::unit_y());
+        camera.yaw = Deg(90.0);
+        assert_near(camera.forward(), Vector3::unit_x());
+        assert_near(camera.right(), Vector3::unit_z());
+    }
+
+    #[test]
+    fn perspective_maps_the_frustum_to_clip_space(){
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 5.0), Deg(90.0), 1.0, 10.0);
+        camera.resize(100, 100);
+        assert_near((camera.view_matrix()*Point3::new(0.0, 0.0, 0.0).to_homogeneous()).truncate(), Vector3::new(0.0, 0.0, -5.0));
+        assert_near(ndc(&camera, Point3::new(0.0, 0.0, 4.0)), Vector3::new(0.0, 0.0, 0.0));
+        assert_near(ndc(&camera, Point3::new(0.0, 0.0, -5.0)), Vector3::new(0.0, 0.0, 1.0));
+        assert_near(ndc(&camera, Point3::new(1.0, 1.0, 4.0)), Vector3::new(1.0, 1.0, 0.0));
+        assert!((camera.world_units_per_pixel(1.0) - 0.02).abs()<1e-6);
+    }
+
+    #[test]
+    fn orthographic_width_follows_the_aspect(){
+        let mut camera = Camera::orthographic(Point3::new(0.0, 0.0, 0.0), 4.0, 1.0, 3.0);
+        camera.resize(200, 100);
+        assert_near(ndc(&camera, Point3::new(4.0, 2.0, -1.0)), Vector3::new(1.0, 1.0, 0.0));
+        assert_near(ndc(&camera, Point3::new(-4.0, -2.0, -3.0)), Vector3::new(-1.0, -1.0, 1.0));
+        assert!((camera.world_units_per_pixel(50.0) - 0.04).abs()<1e-6);
+    }
+
+    #[test]
+    fn orbit_keeps_its_radius_and_clamps_pitch(){
+        let target = Point3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 0.0), Deg(60.0), 0.1, 100.0);
+        let mut orbit = OrbitController::new(target, 5.0);
+        let mut input = Input::default();
+        drag(&mut input, MouseButton::Left, 150.0, -1000.0);
+        orbit.update(&mut camera, &input, 0.016);
+        assert_eq!(camera.yaw, Deg(45.0));
+        assert_eq!(camera.pitch, Deg(MAX_PITCH));
+        assert!(((camera.position - target).magnitude() - 5.0).abs()<1e-4);
+        assert_near(target - camera.position, camera.forward()*5.0);
+
+        input.end_frame();
+        drag(&mut input, MouseButton::Left, 0.0, 2000.0);
+        orbit.update(&mut camera, &input, 0.016);
+        assert_eq!(camera.pitch, Deg(-MAX_PITCH));
+    }
+
+    #[test]
+    fn orbit_zoom_is_clamped(){
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 0.0), Deg(60.0), 0.1, 100.0);
+        let mut orbit = OrbitController::new(Point3::new(0.0, 0.0, 0.0), 5.0);
+        orbit.max_distance = 8.0;
+        let mut input = Input::default();
+        input.handle_event(&Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, 100.0)));
+        orbit.update(&mut camera, &input, 0.016);
+        assert_eq!(orbit.distance, orbit.min_distance);
+        input.end_frame();
+        input.handle_event(&Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, -100.0)));
+        orbit.update(&mut camera, &input, 0.016);
+        assert_eq!(orbit.distance, 8.0);
+        assert_near(camera.position.to_vec(), Vector3::new(0.0, 0.0, 8.0));
+    }
+
+    #[test]
+    fn fly_moves_along_forward_and_right(){
+        let mut camera = Camera::perspective(Point3::new(0.0, 0.0, 0.0), Deg(60.0), 0.1, 100.0);
+        camera.yaw = Deg(90.0);
+        let mut fly = FlyController::new(2.0);
+        let mut input = Input::default();
+        press(&mut input, KeyCode::KeyW);
+        fly.update(&mut camera, &input, 0.5);
+        assert_near(camera.position.to_vec(), Vector3::new(1.0, 0.0, 0.0));
+
+        camera.position = Point3::new(0.0, 0.0, 0.0);
+        input.handle_event(&Event::Focused(false));
+        press(&mut input, KeyCode::KeyD);
+        fly.update(&mut camera, &input, 0.5);
+        assert_near(camera.position.to_vec(), Vector3::new(0.0, 0.0, 1.0));
+
+        // Diagonal movement is no faster than straight movement.
+        camera.position = Point3::new(0.0, 0.0, 0.0);
+        press(&mut input, KeyCode::KeyW);
+        fly.update(&mut camera, &input, 0.5);
+        assert!(((camera.position.to_vec()).magnitude() - 1.0).abs()<1e-4);
+    }
+}
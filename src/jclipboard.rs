@@ -0,0 +1,55 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Where copied text goes and pasted text comes from.
+pub trait Clipboard{
+    fn get(&mut self)->Option<String>;
+    fn set(&mut self, text:String);
+}
+
+/// A clipboard that lives only in this process, for tests and headless runs. Clones share the
+/// same text, so keep one handle to inspect the clipboard after giving another away.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryClipboard{
+    text:Rc<RefCell<Option<String>>>,
+}
+
+impl Clipboard for MemoryClipboard{
+    fn get(&mut self)->Option<String>{
+        self.text.borrow().clone()
+    }
+
+    fn set(&mut self, text:String){
+        *self.text.borrow_mut() = Some(text);
+    }
+}
+
+/// The desktop clipboard (X11, Wayland, Windows or macOS).
+pub struct SystemClipboard{
+    clipboard:arboard::Clipboard,
+}
+
+impl SystemClipboard{
+    pub fn new()->Result<Self, arboard::Error>{
+        Ok(Self{clipboard:arboard::Clipboard::new()?})
+    }
+}
+
+impl Clipboard for SystemClipboard{
+    fn get(&mut self)->Option<String>{
+        match self.clipboard.get_text(){
+            Ok(text)=>Some(text),
+            Err(arboard::Error::ContentNotAvailable)=>None,
+            Err(err)=>{
+                log::error!("failed to read clipboard: {}", err);
+                None
+            }
+        }
+    }
+
+    fn set(&mut self, text:String){
+        if let Err(err) = self.clipboard.set_text(text){
+            log::error!("failed to write clipboard: {}", err);
+        }
+    }
+}
@@ -1,167 +1,687 @@
-use wgpu::util::DeviceExt;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 const MAX_TEXTURE_SIZE:u32 = 4096;
+/// Points scrolled per wheel notch, the same value egui-winit uses.
+const POINTS_PER_SCROLL_LINE:f32 = 50.0;
+const INITIAL_VERTEX_BUFFER_SIZE:u64 = 1<<16;
+const INITIAL_INDEX_BUFFER_SIZE:u64 = 1<<16;
 
+use winit::keyboard::KeyCode;
+use winit::window::CursorIcon;
+
+use crate::jclipboard;
+use crate::jgraph;
+use crate::jinput;
 use crate::jwgpu;
 
+/// Physical winit keys and the egui key each one produces.
+const KEY_MAP:&[(KeyCode, egui::Key)] = &[
+    (KeyCode::Escape, egui::Key::Escape),
+    (KeyCode::Tab, egui::Key::Tab),
+    (KeyCode::Backspace, egui::Key::Backspace),
+    (KeyCode::Enter, egui::Key::Enter),
+    (KeyCode::NumpadEnter, egui::Key::Enter),
+    (KeyCode::Space, egui::Key::Space),
+    (KeyCode::Insert, egui::Key::Insert),
+    (KeyCode::Delete, egui::Key::Delete),
+    (KeyCode::Home, egui::Key::Home),
+    (KeyCode::End, egui::Key::End),
+    (KeyCode::PageUp, egui::Key::PageUp),
+    (KeyCode::PageDown, egui::Key::PageDown),
+    (KeyCode::ArrowDown, egui::Key::ArrowDown),
+    (KeyCode::ArrowLeft, egui::Key::ArrowLeft),
+    (KeyCode::ArrowRight, egui::Key::ArrowRight),
+    (KeyCode::ArrowUp, egui::Key::ArrowUp),
+    (KeyCode::Copy, egui::Key::Copy),
+    (KeyCode::Cut, egui::Key::Cut),
+    (KeyCode::Paste, egui::Key::Paste),
+    (KeyCode::Backquote, egui::Key::Backtick),
+    (KeyCode::Backslash, egui::Key::Backslash),
+    (KeyCode::IntlBackslash, egui::Key::Backslash),
+    (KeyCode::BracketLeft, egui::Key::OpenBracket),
+    (KeyCode::BracketRight, egui::Key::CloseBracket),
+    (KeyCode::Comma, egui::Key::Comma),
+    (KeyCode::NumpadComma, egui::Key::Comma),
+    (KeyCode::Equal, egui::Key::Equals),
+    (KeyCode::NumpadEqual, egui::Key::Equals),
+    (KeyCode::Minus, egui::Key::Minus),
+    (KeyCode::NumpadSubtract, egui::Key::Minus),
+    (KeyCode::NumpadAdd, egui::Key::Plus),
+    (KeyCode::Period, egui::Key::Period),
+    (KeyCode::NumpadDecimal, egui::Key::Period),
+    (KeyCode::Semicolon, egui::Key::Semicolon),
+    (KeyCode::Slash, egui::Key::Slash),
+    (KeyCode::NumpadDivide, egui::Key::Slash),
+    (KeyCode::Digit0, egui::Key::Num0),
+    (KeyCode::Digit1, egui::Key::Num1),
+    (KeyCode::Digit2, egui::Key::Num2),
+    (KeyCode::Digit3, egui::Key::Num3),
+    (KeyCode::Digit4, egui::Key::Num4),
+    (KeyCode::Digit5, egui::Key::Num5),
+    (KeyCode::Digit6, egui::Key::Num6),
+    (KeyCode::Digit7, egui::Key::Num7),
+    (KeyCode::Digit8, egui::Key::Num8),
+    (KeyCode::Digit9, egui::Key::Num9),
+    (KeyCode::Numpad0, egui::Key::Num0),
+    (KeyCode::Numpad1, egui::Key::Num1),
+    (KeyCode::Numpad2, egui::Key::Num2),
+    (KeyCode::Numpad3, egui::Key::Num3),
+    (KeyCode::Numpad4, egui::Key::Num4),
+    (KeyCode::Numpad5, egui::Key::Num5),
+    (KeyCode::Numpad6, egui::Key::Num6),
+    (KeyCode::Numpad7, egui::Key::Num7),
+    (KeyCode::Numpad8, egui::Key::Num8),
+    (KeyCode::Numpad9, egui::Key::Num9),
+    (KeyCode::KeyA, egui::Key::A),
+    (KeyCode::KeyB, egui::Key::B),
+    (KeyCode::KeyC, egui::Key::C),
+    (KeyCode::KeyD, egui::Key::D),
+    (KeyCode::KeyE, egui::Key::E),
+    (KeyCode::KeyF, egui::Key::F),
+    (KeyCode::KeyG, egui::Key::G),
+    (KeyCode::KeyH, egui::Key::H),
+    (KeyCode::KeyI, egui::Key::I),
+    (KeyCode::KeyJ, egui::Key::J),
+    (KeyCode::KeyK, egui::Key::K),
+    (KeyCode::KeyL, egui::Key::L),
+    (KeyCode::KeyM, egui::Key::M),
+    (KeyCode::KeyN, egui::Key::N),
+    (KeyCode::KeyO, egui::Key::O),
+    (KeyCode::KeyP, egui::Key::P),
+    (KeyCode::KeyQ, egui::Key::Q),
+    (KeyCode::KeyR, egui::Key::R),
+    (KeyCode::KeyS, egui::Key::S),
+    (KeyCode::KeyT, egui::Key::T),
+    (KeyCode::KeyU, egui::Key::U),
+    (KeyCode::KeyV, egui::Key::V),
+    (KeyCode::KeyW, egui::Key::W),
+    (KeyCode::KeyX, egui::Key::X),
+    (KeyCode::KeyY, egui::Key::Y),
+    (KeyCode::KeyZ, egui::Key::Z),
+    (KeyCode::F1, egui::Key::F1),
+    (KeyCode::F2, egui::Key::F2),
+    (KeyCode::F3, egui::Key::F3),
+    (KeyCode::F4, egui::Key::F4),
+    (KeyCode::F5, egui::Key::F5),
+    (KeyCode::F6, egui::Key::F6),
+    (KeyCode::F7, egui::Key::F7),
+    (KeyCode::F8, egui::Key::F8),
+    (KeyCode::F9, egui::Key::F9),
+    (KeyCode::F10, egui::Key::F10),
+    (KeyCode::F11, egui::Key::F11),
+    (KeyCode::F12, egui::Key::F12),
+    (KeyCode::F13, egui::Key::F13),
+    (KeyCode::F14, egui::Key::F14),
+    (KeyCode::F15, egui::Key::F15),
+    (KeyCode::F16, egui::Key::F16),
+    (KeyCode::F17, egui::Key::F17),
+    (KeyCode::F18, egui::Key::F18),
+    (KeyCode::F19, egui::Key::F19),
+    (KeyCode::F20, egui::Key::F20),
+    (KeyCode::F21, egui::Key::F21),
+    (KeyCode::F22, egui::Key::F22),
+    (KeyCode::F23, egui::Key::F23),
+    (KeyCode::F24, egui::Key::F24),
+    (KeyCode::F25, egui::Key::F25),
+    (KeyCode::F26, egui::Key::F26),
+    (KeyCode::F27, egui::Key::F27),
+    (KeyCode::F28, egui::Key::F28),
+    (KeyCode::F29, egui::Key::F29),
+    (KeyCode::F30, egui::Key::F30),
+    (KeyCode::F31, egui::Key::F31),
+    (KeyCode::F32, egui::Key::F32),
+    (KeyCode::F33, egui::Key::F33),
+    (KeyCode::F34, egui::Key::F34),
+    (KeyCode::F35, egui::Key::F35),
+];
+
+/// Keys egui only knows as shifted symbols, recognised from the text a key press produced.
+const SYMBOL_KEYS:&[(&str, egui::Key)] = &[
+    (":", egui::Key::Colon),
+    ("|", egui::Key::Pipe),
+    ("?", egui::Key::Questionmark),
+    ("+", egui::Key::Plus),
+];
+
+/// State shared between paint callbacks across frames, keyed by type: pipelines, buffers
+/// and bind groups a callback creates once and reuses.
+#[derive(Default)]
+pub struct CallbackResources{
+    resources:HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl CallbackResources{
+    pub fn insert<T:'static>(&mut self, resource:T){
+        self.resources.insert(TypeId::of::<T>(), Box::new(resource));
+    }
+
+    pub fn get<T:'static>(&self)->Option<&T>{
+        self.resources.get(&TypeId::of::<T>()).and_then(|resource| resource.downcast_ref())
+    }
+
+    pub fn get_mut<T:'static>(&mut self)->Option<&mut T>{
+        self.resources.get_mut(&TypeId::of::<T>()).and_then(|resource| resource.downcast_mut())
+    }
+
+    pub fn remove<T:'static>(&mut self)->Option<T>{
+        let resource = self.resources.remove(&TypeId::of::<T>())?;
+        resource.downcast().ok().map(|resource| *resource)
+    }
+}
+
+/// Custom wgpu drawing inside an egui rect, e.g. a 3D scene preview or a plot.
+pub trait CallbackTrait:Send + Sync{
+    /// Runs before the egui render pass starts; upload buffers or record passes into `encoder` here.
+    /// Returned command buffers are submitted before the egui frame.
+    fn prepare(
+        &self,
+        _device:&wgpu::Device,
+        _queue:&wgpu::Queue,
+        _encoder:&mut wgpu::CommandEncoder,
+        _resources:&mut CallbackResources,
+    )->Vec<wgpu::CommandBuffer>{
+        Vec::new()
+    }
+
+    /// Draws into the egui render pass. The viewport is set to the callback rect and the
+    /// scissor rect to its clip rect; `info` has both in pixels.
+    fn paint<'a>(&'a self, info:&egui::PaintCallbackInfo, render_pass:&mut wgpu::RenderPass<'a>, resources:&'a CallbackResources);
+}
+
+/// The value jegui expects in `egui::PaintCallback::callback`.
+pub struct Callback(Box<dyn CallbackTrait>);
+
+impl Callback{
+    /// Wraps `callback` into a shape for `ui.painter().add(..)`, drawn over `rect`.
+    pub fn new_paint_callback(rect:egui::Rect, callback:impl CallbackTrait + 'static)->egui::PaintCallback{
+        egui::PaintCallback{rect, callback:Arc::new(Callback(Box::new(callback)))}
+    }
+}
+
+/// A texture egui can draw with: one egui manages (font atlas, `ctx.load_texture` images)
+/// or one the app registered with `Core::register_native_texture`.
+enum Texture{
+    Managed(jwgpu::JTexture),
+    Native{bind_group:wgpu::BindGroup},
+}
+
+impl Texture{
+    fn bind_group(&self)->&wgpu::BindGroup{
+        match self{
+            Texture::Managed(texture)=>&texture.bind_group,
+            Texture::Native{bind_group}=>bind_group,
+        }
+    }
+}
+
+enum Draw{
+    /// One indexed draw sampling a single texture.
+    Mesh{
+        texture_id:egui::TextureId,
+        indices:Range<u32>,
+        base_vertex:i32,
+        /// x, y, width and height in physical pixels.
+        scissor:[u32; 4],
+    },
+    Callback{
+        /// Holds a `Callback`, checked in `prepare`.
+        callback:Arc<dyn Any + Send + Sync>,
+        info:egui::PaintCallbackInfo,
+        scissor:[u32; 4],
+    },
+}
+
 pub struct Core{
     mouse_position:egui::Pos2,
-    scale:f32,
-    sizex:u32,
-    sizey:u32,
+    modifiers:egui::Modifiers,
+    clipboard:Box<dyn jclipboard::Clipboard>,
+    open_url:Box<dyn FnMut(&egui::OpenUrl)>,
+    cursor_icon:Option<egui::CursorIcon>,
+    ime_allowed:bool,
+    ime_cursor_rect:Option<egui::Rect>,
+    ime_composing:bool,
+    mutable_text_under_cursor:bool,
+    /// Physical pixels per logical pixel of the window.
+    scale_factor:f32,
+    textures:HashMap<egui::TextureId, Texture>,
+    next_user_texture_id:u64,
     ctx:egui::Context,
     pipeline:jwgpu::JRenderPipeline,
+    vertex_buffer:jwgpu::JBuffer,
+    index_buffer:jwgpu::JBuffer,
+    callback_resources:CallbackResources,
+    draws:Vec<Draw>,
+    /// Freed by egui in the last frame; dropped once that frame has been painted.
+    textures_to_free:Vec<egui::TextureId>,
+    target_size:[u32; 2],
+    /// `MAX_TEXTURE_SIZE`, or less if the device can't make textures that large.
+    max_texture_side:u32,
 }
 
 impl Core{
 
-    fn convert_winit_keycode_to_egui_key(winit_keycode:winit::keyboard::KeyCode) -> Option<egui::Key>{
-        match winit_keycode {
-            winit::keyboard::KeyCode::Backquote=>None,
-            winit::keyboard::KeyCode::Backslash=>Some(egui::Key::Backslash),
-            winit::keyboard::KeyCode::BracketLeft=>Some(egui::Key::OpenBracket),
-            winit::keyboard::KeyCode::BracketRight=>Some(egui::Key::CloseBracket),
-            winit::keyboard::KeyCode::Comma=>Some(egui::Key::Comma),
-            winit::keyboard::KeyCode::Digit0=>Some(egui::Key::Num0),
-            winit::keyboard::KeyCode::Digit1=>Some(egui::Key::Num1),
-            winit::keyboard::KeyCode::Digit2=>Some(egui::Key::Num2),
-            winit::keyboard::KeyCode::Digit3=>Some(egui::Key::Num3),
-            winit::keyboard::KeyCode::Digit4=>Some(egui::Key::Num4),
-            winit::keyboard::KeyCode::Digit5=>Some(egui::Key::Num5),
-            winit::keyboard::KeyCode::Digit6=>Some(egui::Key::Num6),
-            winit::keyboard::KeyCode::Digit7=>Some(egui::Key::Num7),
-            winit::keyboard::KeyCode::Digit8=>Some(egui::Key::Num8),
-            winit::keyboard::KeyCode::Digit9=>Some(egui::Key::Num9),
-            winit::keyboard::KeyCode::Equal=>Some(egui::Key::Equals),
-            winit::keyboard::KeyCode::IntlBackslash=>Some(egui::Key::Backslash),
-            winit::keyboard::KeyCode::IntlRo=>None,
-            winit::keyboard::KeyCode::IntlYen=>None,
-            winit::keyboard::KeyCode::KeyA=>Some(egui::Key::A),
-            winit::keyboard::KeyCode::KeyB=>Some(egui::Key::B),
-            winit::keyboard::KeyCode::KeyC=>Some(egui::Key::C),
-            winit::keyboard::KeyCode::KeyD=>Some(egui::Key::D),
-            winit::keyboard::KeyCode::KeyE=>Some(egui::Key::E),
-            winit::keyboard::KeyCode::KeyF=>Some(egui::Key::F),
-            winit::keyboard::KeyCode::KeyG=>Some(egui::Key::G),
-            winit::keyboard::KeyCode::KeyH=>Some(egui::Key::H),
-            winit::keyboard::KeyCode::KeyI=>Some(egui::Key::I),
-            winit::keyboard::KeyCode::KeyJ=>Some(egui::Key::J),
-            winit::keyboard::KeyCode::KeyK=>Some(egui::Key::K),
-            winit::keyboard::KeyCode::KeyL=>Some(egui::Key::L),
-            winit::keyboard::KeyCode::KeyM=>Some(egui::Key::M),
-            winit::keyboard::KeyCode::KeyN=>Some(egui::Key::N),
-            winit::keyboard::KeyCode::KeyO=>Some(egui::Key::O),
-            winit::keyboard::KeyCode::KeyP=>Some(egui::Key::P),
-            winit::keyboard::KeyCode::KeyQ=>Some(egui::Key::Q),
-            winit::keyboard::KeyCode::KeyR=>Some(egui::Key::R),
-            winit::keyboard::KeyCode::KeyS=>Some(egui::Key::S),
-            winit::keyboard::KeyCode::KeyT=>Some(egui::Key::T),
-            winit::keyboard::KeyCode::KeyU=>Some(egui::Key::U),
-            winit::keyboard::KeyCode::KeyV=>Some(egui::Key::V),
-            winit::keyboard::KeyCode::KeyW=>Some(egui::Key::W),
-            winit::keyboard::KeyCode::KeyX=>Some(egui::Key::X),
-            winit::keyboard::KeyCode::KeyY=>Some(egui::Key::Y),
-            winit::keyboard::KeyCode::KeyZ=>Some(egui::Key::Z),
-            winit::keyboard::KeyCode::Enter=>Some(egui::Key::Enter),
-            winit::keyboard::KeyCode::Backspace=>Some(egui::Key::Backspace),
-            winit::keyboard::KeyCode::Tab=>Some(egui::Key::Tab),
-            winit::keyboard::KeyCode::ArrowLeft=>Some(egui::Key::ArrowLeft),
-            winit::keyboard::KeyCode::ArrowRight=>Some(egui::Key::ArrowRight),
-            winit::keyboard::KeyCode::ArrowUp=>Some(egui::Key::ArrowUp),
-            winit::keyboard::KeyCode::ArrowDown=>Some(egui::Key::ArrowDown),
-            _ => None,
+    /// Not a control character or in a private use area, where some platforms put function keys.
+    fn is_printable_char(c:char)->bool{
+        let private_use = ('\u{e000}'..='\u{f8ff}').contains(&c)
+            || ('\u{f0000}'..='\u{ffffd}').contains(&c)
+            || ('\u{100000}'..='\u{10fffd}').contains(&c);
+        !private_use && !c.is_control()
+    }
+
+    fn convert_winit_keycode_to_egui_key(winit_keycode:KeyCode) -> Option<egui::Key>{
+        KEY_MAP.iter().find(|(code, _)| *code==winit_keycode).map(|(_, key)| *key)
+    }
+
+    fn convert_text_to_egui_key(text:&str)->Option<egui::Key>{
+        SYMBOL_KEYS.iter().find(|(symbol, _)| *symbol==text).map(|(_, key)| *key)
+    }
+
+    fn convert_winit_modifiers(modifiers:jinput::ModifiersState)->egui::Modifiers{
+        egui::Modifiers{
+            alt:modifiers.alt_key(),
+            ctrl:modifiers.control_key(),
+            shift:modifiers.shift_key(),
+            mac_cmd:cfg!(target_os = "macos") && modifiers.super_key(),
+            command:if cfg!(target_os = "macos") { modifiers.super_key() } else { modifiers.control_key() },
+        }
+    }
+
+    fn convert_winit_mouse_button(button:jinput::MouseButton)->Option<egui::PointerButton>{
+        match button{
+            jinput::MouseButton::Left=>Some(egui::PointerButton::Primary),
+            jinput::MouseButton::Right=>Some(egui::PointerButton::Secondary),
+            jinput::MouseButton::Middle=>Some(egui::PointerButton::Middle),
+            jinput::MouseButton::Back=>Some(egui::PointerButton::Extra1),
+            jinput::MouseButton::Forward=>Some(egui::PointerButton::Extra2),
+            jinput::MouseButton::Other(_)=>None,
+        }
+    }
+
+    /// The Copy/Cut/Paste event for a clipboard shortcut or dedicated clipboard key, if `key` is one.
+    fn clipboard_event(&mut self, key:egui::Key)->Option<egui::Event>{
+        let modifiers = self.modifiers;
+        let is_cut = key==egui::Key::Cut
+            || (modifiers.command && key==egui::Key::X)
+            || (cfg!(not(target_os = "macos")) && modifiers.shift && key==egui::Key::Delete);
+        let is_copy = key==egui::Key::Copy
+            || (modifiers.command && key==egui::Key::C)
+            || (cfg!(not(target_os = "macos")) && modifiers.ctrl && key==egui::Key::Insert);
+        let is_paste = key==egui::Key::Paste
+            || (modifiers.command && key==egui::Key::V)
+            || (cfg!(not(target_os = "macos")) && modifiers.shift && key==egui::Key::Insert);
+        if is_cut{
+            Some(egui::Event::Cut)
+        }else if is_copy{
+            Some(egui::Event::Copy)
+        }else if is_paste{
+            let text = self.clipboard.get().unwrap_or_default().replace("\r\n", "\n");
+            if text.is_empty() { None } else { Some(egui::Event::Paste(text)) }
+        }else{
+            None
+        }
+    }
+
+    /// Replaces the clipboard used for copy, cut and paste.
+    pub fn set_clipboard(&mut self, clipboard:Box<dyn jclipboard::Clipboard>){
+        self.clipboard = clipboard;
+    }
+
+    fn convert_egui_cursor_icon(icon:egui::CursorIcon)->Option<CursorIcon>{
+        match icon{
+            egui::CursorIcon::None=>None,
+            egui::CursorIcon::Alias=>Some(CursorIcon::Alias),
+            egui::CursorIcon::AllScroll=>Some(CursorIcon::AllScroll),
+            egui::CursorIcon::Cell=>Some(CursorIcon::Cell),
+            egui::CursorIcon::ContextMenu=>Some(CursorIcon::ContextMenu),
+            egui::CursorIcon::Copy=>Some(CursorIcon::Copy),
+            egui::CursorIcon::Crosshair=>Some(CursorIcon::Crosshair),
+            egui::CursorIcon::Default=>Some(CursorIcon::Default),
+            egui::CursorIcon::Grab=>Some(CursorIcon::Grab),
+            egui::CursorIcon::Grabbing=>Some(CursorIcon::Grabbing),
+            egui::CursorIcon::Help=>Some(CursorIcon::Help),
+            egui::CursorIcon::Move=>Some(CursorIcon::Move),
+            egui::CursorIcon::NoDrop=>Some(CursorIcon::NoDrop),
+            egui::CursorIcon::NotAllowed=>Some(CursorIcon::NotAllowed),
+            egui::CursorIcon::PointingHand=>Some(CursorIcon::Pointer),
+            egui::CursorIcon::Progress=>Some(CursorIcon::Progress),
+            egui::CursorIcon::ResizeHorizontal=>Some(CursorIcon::EwResize),
+            egui::CursorIcon::ResizeNeSw=>Some(CursorIcon::NeswResize),
+            egui::CursorIcon::ResizeNwSe=>Some(CursorIcon::NwseResize),
+            egui::CursorIcon::ResizeVertical=>Some(CursorIcon::NsResize),
+            egui::CursorIcon::ResizeEast=>Some(CursorIcon::EResize),
+            egui::CursorIcon::ResizeSouthEast=>Some(CursorIcon::SeResize),
+            egui::CursorIcon::ResizeSouth=>Some(CursorIcon::SResize),
+            egui::CursorIcon::ResizeSouthWest=>Some(CursorIcon::SwResize),
+            egui::CursorIcon::ResizeWest=>Some(CursorIcon::WResize),
+            egui::CursorIcon::ResizeNorthWest=>Some(CursorIcon::NwResize),
+            egui::CursorIcon::ResizeNorth=>Some(CursorIcon::NResize),
+            egui::CursorIcon::ResizeNorthEast=>Some(CursorIcon::NeResize),
+            egui::CursorIcon::ResizeColumn=>Some(CursorIcon::ColResize),
+            egui::CursorIcon::ResizeRow=>Some(CursorIcon::RowResize),
+            egui::CursorIcon::Text=>Some(CursorIcon::Text),
+            egui::CursorIcon::VerticalText=>Some(CursorIcon::VerticalText),
+            egui::CursorIcon::Wait=>Some(CursorIcon::Wait),
+            egui::CursorIcon::ZoomIn=>Some(CursorIcon::ZoomIn),
+            egui::CursorIcon::ZoomOut=>Some(CursorIcon::ZoomOut),
         }
     }
 
+    /// Replaces what happens when egui asks to open a URL, e.g. after a click on a `ui.hyperlink`.
+    /// By default the URL is opened in the system browser.
+    pub fn set_open_url_handler(&mut self, handler:impl FnMut(&egui::OpenUrl) + 'static){
+        self.open_url = Box::new(handler);
+    }
+
+    /// Whether the pointer was over editable text during the last frame.
+    pub fn mutable_text_under_cursor(&self)->bool{
+        self.mutable_text_under_cursor
+    }
+
+    fn handle_platform_output(&mut self, jwgpu_core:&jwgpu::Core, platform_output:egui::PlatformOutput){
+        if let Some(open_url) = &platform_output.open_url{
+            (self.open_url)(open_url);
+        }
+        if !platform_output.copied_text.is_empty(){
+            self.clipboard.set(platform_output.copied_text);
+        }
+        self.mutable_text_under_cursor = platform_output.mutable_text_under_cursor;
+        let Some(window) = jwgpu_core.window() else {
+            return;
+        };
+        if self.cursor_icon!=Some(platform_output.cursor_icon){
+            self.cursor_icon = Some(platform_output.cursor_icon);
+            match Core::convert_egui_cursor_icon(platform_output.cursor_icon){
+                Some(icon)=>{
+                    window.set_cursor_visible(true);
+                    window.set_cursor_icon(icon);
+                }
+                None=>window.set_cursor_visible(false),
+            }
+        }
+        let ime_allowed = platform_output.ime.is_some();
+        if self.ime_allowed!=ime_allowed{
+            self.ime_allowed = ime_allowed;
+            window.set_ime_allowed(ime_allowed);
+        }
+        // Place the candidate window at the text cursor (not the whole text edit), in physical pixels.
+        let ime_cursor_rect = platform_output.ime.map(|ime| ime.cursor_rect);
+        if let Some(rect) = ime_cursor_rect.filter(|rect| self.ime_cursor_rect!=Some(*rect)){
+            let pixels_per_point = self.pixels_per_point();
+            window.set_ime_cursor_area(
+                winit::dpi::PhysicalPosition::new(rect.min.x*pixels_per_point, rect.min.y*pixels_per_point),
+                winit::dpi::PhysicalSize::new(rect.width()*pixels_per_point, rect.height()*pixels_per_point),
+            );
+        }
+        self.ime_cursor_rect = ime_cursor_rect;
+    }
+
+    fn push_scroll_events(&self, events:&mut Vec<egui::Event>, delta:&jinput::MouseScrollDelta){
+        let (unit, delta) = match *delta{
+            jinput::MouseScrollDelta::LineDelta(x, y)=>(egui::MouseWheelUnit::Line, egui::vec2(x, y)),
+            jinput::MouseScrollDelta::PixelDelta(delta)=>{
+                (egui::MouseWheelUnit::Point, egui::vec2(delta.x as f32, delta.y as f32)/self.pixels_per_point())
+            }
+        };
+        events.push(egui::Event::MouseWheel{unit, delta, modifiers:self.modifiers});
+        let points = match unit{
+            egui::MouseWheelUnit::Line=>delta*POINTS_PER_SCROLL_LINE,
+            _=>delta,
+        };
+        if self.modifiers.ctrl || self.modifiers.command{
+            events.push(egui::Event::Zoom((points.y/200.0).exp()));
+        }else if self.modifiers.shift{
+            // Shift+wheel scrolls horizontally.
+            events.push(egui::Event::Scroll(egui::vec2(points.x + points.y, 0.0)));
+        }else{
+            events.push(egui::Event::Scroll(points));
+        }
+    }
+
+    /// Runs `run_ui` on this frame's input and draws the result over the current frame.
+    /// Use `run_ui`, `prepare` and `add_paint_pass` (or `paint`) instead to draw the UI as part
+    /// of a render graph (or render pass) of your own.
     pub fn run(&mut self,
         jwgpu_core:&mut jwgpu::Core,
         run_ui: impl FnOnce(&egui::Context)
-    ) {
+    ) -> Result<(), jwgpu::Error> {
+        let full_output = self.run_ui(jwgpu_core, run_ui);
+        let (mut graph, target) = jwgpu_core.render_graph();
+        let command_buffers = self.prepare(jwgpu_core, graph.encoder(), full_output)?;
+        graph.add_command_buffers(command_buffers);
+        if let Some(target) = target{
+            self.add_paint_pass(&mut graph, target);
+        }
+        jwgpu_core.execute_graph(graph)
+    }
+
+    /// Adds a pass painting the last prepared output over `target`, after every earlier pass
+    /// writing it.
+    pub fn add_paint_pass<'a>(&'a self, graph:&mut jgraph::RenderGraph<'a>, target:jgraph::ResourceId){
+        graph.add_pass("egui", |pass| { pass.read(target).write(target); }, move |ctx| {
+            let mut rpass =
+                ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("egui_pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: ctx.resources.view(target),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+            self.paint(&mut rpass);
+        });
+    }
+
+    /// Feeds this frame's events to egui and runs `run_ui`.
+    pub fn run_ui(&mut self, jwgpu_core:&mut jwgpu::Core, run_ui:impl FnOnce(&egui::Context))->egui::FullOutput{
         let raw_input = self.handle_events(jwgpu_core);
-        let full_output = self.ctx.run(raw_input,run_ui);
-        self.render(full_output, jwgpu_core);
+        self.ctx.run(raw_input, run_ui)
     }
     
     fn handle_events(&mut self, jwgpu_core: &mut jwgpu::Core)->egui::RawInput{
         let mut events:Vec<egui::Event> = Vec::new();
         for event in &jwgpu_core.events{
             match event {
-                winit::event::WindowEvent::CursorMoved { device_id:_, position }=>{
-                    self.mouse_position = egui::pos2(position.x as f32/self.scale, position.y as f32/self.scale);
+                jinput::Event::CursorMoved { x, y }=>{
+                    self.mouse_position = egui::pos2(*x as f32, *y as f32)/self.pixels_per_point();
                     events.push(egui::Event::PointerMoved(self.mouse_position));
                 },
-                winit::event::WindowEvent::MouseInput { device_id:_device_id, state, button:_button }=>{
-                    events.push(egui::Event::PointerButton { 
-                        pos: self.mouse_position,
-                        button: egui::PointerButton::Primary, 
-                        pressed: state.is_pressed(), 
-                        modifiers: egui::Modifiers::NONE });
-    
+                jinput::Event::CursorLeft=>events.push(egui::Event::PointerGone),
+                jinput::Event::MouseInput { state, button }=>{
+                    if let Some(button) = Core::convert_winit_mouse_button(*button){
+                        events.push(egui::Event::PointerButton { 
+                            pos: self.mouse_position,
+                            button, 
+                            pressed: state.is_pressed(), 
+                            modifiers: self.modifiers });
+                    }
                 },
-                winit::event::WindowEvent::KeyboardInput { device_id:_device_id, event, is_synthetic:_is_synthetic } => {
-                    let mut keycode:Option<egui::Key> = None;
-                    match event.physical_key{
-                        winit::keyboard::PhysicalKey::Code(code)=>{
-                            match Core::convert_winit_keycode_to_egui_key(code){
-                                Some(key)=>{
-                                    events.push(egui::Event::Key { 
-                                        key, 
-                                        physical_key: Some(key), 
-                                        pressed:event.state.is_pressed() , 
-                                        repeat:event.repeat, 
-                                        modifiers: egui::Modifiers::NONE });
-                                    keycode = Some(key);
-                                },
-                                None=>{}
-                            }
-                            
-                        },
-                        winit::keyboard::PhysicalKey::Unidentified(_native_keycode)=>{}
+                jinput::Event::MouseWheel(delta)=>self.push_scroll_events(&mut events, delta),
+                jinput::Event::ModifiersChanged(modifiers)=>self.modifiers = Core::convert_winit_modifiers(*modifiers),
+                jinput::Event::KeyboardInput { physical_key, text, state, repeat } => {
+                    let physical = match *physical_key{
+                        jinput::PhysicalKey::Code(code)=>Core::convert_winit_keycode_to_egui_key(code),
+                        jinput::PhysicalKey::Unidentified(_)=>None,
+                    };
+                    let logical = text.as_deref().and_then(Core::convert_text_to_egui_key).or(physical);
+                    if let Some(event) = logical.filter(|_| state.is_pressed()).and_then(|key| self.clipboard_event(key)){
+                        events.push(event);
+                        continue;
                     }
-                    match &event.text{
-                        Some(text) => {
-                            let do_event = match keycode{
-                                Some(key) => {
-                                    match key{
-                                        egui::Key::Backspace=>false,
-                                        egui::Key::Enter=>false,
-                                        egui::Key::Tab=>false,
-                                        _=>true,
-                                    }
-                                }
-                                None => true,
-                            };
-                            if do_event{
-                                events.push(egui::Event::Text(text.to_string()));
-                            }
-                        },
-                        None => {}
+                    if let Some(key) = logical{
+                        events.push(egui::Event::Key { 
+                            key, 
+                            physical_key: physical, 
+                            pressed:state.is_pressed() , 
+                            repeat:*repeat, 
+                            modifiers: self.modifiers });
+                    }
+                    // Like egui-winit: shortcuts and releases type nothing, and neither do the control
+                    // characters of Enter, Tab, Backspace, Escape and Delete, which egui handles through
+                    // the key events above.
+                    let shortcut = self.modifiers.ctrl || self.modifiers.command || self.modifiers.mac_cmd;
+                    if let Some(text) = text.as_ref().filter(|_| state.is_pressed() && !shortcut){
+                        if !text.is_empty() && text.chars().all(Core::is_printable_char){
+                            events.push(egui::Event::Text(text.to_string()));
+                        }
                     }
-                    
                 },
-                winit::event::WindowEvent::Resized(new_size) => {
-                    jwgpu_core.config.width = new_size.width.max(1);
-                    jwgpu_core.config.height = new_size.height.max(1);
-                    jwgpu_core.surface.configure(&jwgpu_core.device, &jwgpu_core.config);
-                    let view = cgmath::ortho(0.0, jwgpu_core.config.width as f32, jwgpu_core.config.height as f32, 0.0, -1.0, 1.0)
-                        * cgmath::Matrix4::from_scale(self.scale)
-                        * jwgpu::OPENGL_TO_WGPU_MATRIX;
-                    let camera_uniform = jwgpu::CameraUniform{view:view.into()};
-                    jwgpu_core.queue.write_buffer(&self.pipeline.camera.buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
-                }
+                jinput::Event::Ime(ime)=>match ime{
+                    jinput::Ime::Preedit(text, Some(_))=>{
+                        if !self.ime_composing{
+                            self.ime_composing = true;
+                            events.push(egui::Event::CompositionStart);
+                        }
+                        events.push(egui::Event::CompositionUpdate(text.clone()));
+                    }
+                    jinput::Ime::Commit(text)=>{
+                        self.ime_composing = false;
+                        events.push(egui::Event::CompositionEnd(text.clone()));
+                    }
+                    jinput::Ime::Preedit(_, None) | jinput::Ime::Enabled | jinput::Ime::Disabled=>{}
+                },
+                jinput::Event::Focused(false)=>self.modifiers = egui::Modifiers::NONE,
+                jinput::Event::ScaleFactorChanged(scale_factor)=>self.scale_factor = *scale_factor as f32,
                 _=>{}
             }
         }
-        egui::RawInput{
+        let screen_size = egui::vec2(jwgpu_core.config.width as f32, jwgpu_core.config.height as f32)/self.pixels_per_point();
+        let mut raw_input = egui::RawInput{
             events,
-            max_texture_side:Some(self.pipeline.texture.width as usize),
-            screen_rect:Some(egui::Rect{min:egui::pos2(10.0,10.0), max:egui::pos2(200.0,500.0)}),
+            modifiers:self.modifiers,
+            max_texture_side:Some(self.max_texture_side as usize),
+            screen_rect:Some(egui::Rect::from_min_size(egui::Pos2::ZERO, screen_size)),
+            // The engine's time rather than egui's own, so animations follow replays and fixed clocks.
+            time:Some(jwgpu_core.time.elapsed().as_secs_f64()),
             ..Default::default()
+        };
+        raw_input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(self.scale_factor);
+        raw_input
+    }
+
+    /// Whether the pointer is over or dragging UI, so the app should ignore mouse input.
+    pub fn wants_pointer_input(&self)->bool{
+        self.ctx.wants_pointer_input()
+    }
+
+    /// Whether a widget has keyboard focus, so the app should ignore key input.
+    pub fn wants_keyboard_input(&self)->bool{
+        self.ctx.wants_keyboard_input()
+    }
+
+    /// Physical pixels per egui point: the window scale factor times the zoom.
+    pub fn pixels_per_point(&self)->f32{
+        self.scale_factor*self.ctx.zoom_factor()
+    }
+
+    pub fn zoom(&self)->f32{
+        self.ctx.zoom_factor()
+    }
+
+    /// Scales the UI on top of the window scale factor. egui also changes it on Ctrl+Plus/Minus.
+    pub fn set_zoom(&mut self, zoom:f32){
+        self.ctx.set_zoom_factor(zoom);
+    }
+
+    /// Makes `view` drawable by egui, e.g. a rendered 3D viewport shown with `ui.image((id, size))`.
+    /// The view must be of a filterable float 2D texture.
+    pub fn register_native_texture(&mut self, jwgpu_core:&jwgpu::Core, view:&wgpu::TextureView)->egui::TextureId{
+        let id = egui::TextureId::User(self.next_user_texture_id);
+        self.next_user_texture_id += 1;
+        self.update_native_texture(jwgpu_core, id, view);
+        id
+    }
+
+    /// Points a registered texture id at a new view, e.g. after the texture was recreated on resize.
+    pub fn update_native_texture(&mut self, jwgpu_core:&jwgpu::Core, id:egui::TextureId, view:&wgpu::TextureView){
+        let bind_group = jwgpu::create_texture_bind_group(&jwgpu_core.device, &self.pipeline.texture_bind_group_layout, view);
+        self.textures.insert(id, Texture::Native{bind_group});
+    }
+
+    pub fn free_native_texture(&mut self, id:egui::TextureId){
+        self.textures.remove(&id);
+    }
+
+    fn update_texture(&mut self, jwgpu_core:&jwgpu::Core, id:egui::TextureId, delta:&egui::epaint::ImageDelta)->Result<(), jwgpu::Error>{
+        let [width, height] = delta.image.size();
+        if width>self.max_texture_side as usize || height>self.max_texture_side as usize{
+            return Err(jwgpu::Error::TextureTooLarge{width:width as u32, height:height as u32, max:self.max_texture_side});
+        }
+        let pixels:Vec<u8> = match &delta.image{
+            // Coverage becomes premultiplied white with sRGB-encoded colour, like egui's own renderers.
+            egui::ImageData::Font(font)=>font.srgba_pixels(None).flat_map(|p| p.to_array()).collect(),
+            egui::ImageData::Color(color)=>color.pixels.iter().flat_map(|p| p.to_array()).collect(),
+        };
+        let size = [width as u32, height as u32];
+        match delta.pos{
+            // Whole image: egui sends these for new textures and when the font atlas grows.
+            None=>{
+                let texture = jwgpu::JTexture::new(&jwgpu_core.device, &self.pipeline.texture_bind_group_layout, size[0], size[1])?;
+                texture.write_texture(&jwgpu_core.queue, &pixels);
+                self.textures.insert(id, Texture::Managed(texture));
+            }
+            Some([x, y])=>match self.textures.get(&id){
+                Some(Texture::Managed(texture))=>texture.write_region(&jwgpu_core.queue, [x as u32, y as u32], size, &pixels),
+                _=>log::warn!("ignoring partial update of unknown texture {:?}", id),
+            },
+        }
+        Ok(())
+    }
+
+    /// Drops textures egui no longer uses; called once the frame that may still draw them has been painted.
+    fn free_textures(&mut self, ids:&[egui::TextureId]){
+        for id in ids{
+            self.textures.remove(id);
         }
     }
 
-    pub fn new(jwgpu_core: &mut jwgpu::Core, scale:f32)->Core{
+    /// Persistent state for paint callbacks; insert pipelines and buffers here before the first frame.
+    pub fn callback_resources_mut(&mut self)->&mut CallbackResources{
+        &mut self.callback_resources
+    }
+
+    /// `clip_rect` in physical pixels, clamped to the render target; `None` if nothing is left of it.
+    fn scissor_rect(clip_rect:egui::Rect, pixels_per_point:f32, jwgpu_core:&jwgpu::Core)->Option<[u32; 4]>{
+        let width = jwgpu_core.config.width as f32;
+        let height = jwgpu_core.config.height as f32;
+        let min_x = (clip_rect.min.x*pixels_per_point).round().clamp(0.0, width) as u32;
+        let min_y = (clip_rect.min.y*pixels_per_point).round().clamp(0.0, height) as u32;
+        let max_x = (clip_rect.max.x*pixels_per_point).round().clamp(0.0, width) as u32;
+        let max_y = (clip_rect.max.y*pixels_per_point).round().clamp(0.0, height) as u32;
+        if max_x<=min_x || max_y<=min_y{
+            return None;
+        }
+        Some([min_x, min_y, max_x - min_x, max_y - min_y])
+    }
+
+    fn update_camera(&self, jwgpu_core:&jwgpu::Core, pixels_per_point:f32){
+        self.pipeline.camera.update(&jwgpu_core.queue, Core::camera_uniform(jwgpu_core, pixels_per_point));
+    }
+
+    fn camera_uniform(jwgpu_core:&jwgpu::Core, pixels_per_point:f32)->jwgpu::CameraUniform{
+        use cgmath::SquareMatrix;
+        jwgpu::CameraUniform::new(cgmath::Matrix4::identity(), Core::projection_matrix(jwgpu_core, pixels_per_point))
+    }
+
+    /// Maps egui points to clip space over the whole render target.
+    fn projection_matrix(jwgpu_core:&jwgpu::Core, pixels_per_point:f32)->cgmath::Matrix4<f32>{
+        jwgpu::OPENGL_TO_WGPU_MATRIX
+            * cgmath::ortho(0.0, jwgpu_core.config.width as f32, jwgpu_core.config.height as f32, 0.0, -1.0, 1.0)
+            * cgmath::Matrix4::from_scale(pixels_per_point)
+    }
+
+    /// Creates the UI renderer. `zoom` scales the UI on top of the window scale factor.
+    pub fn new(jwgpu_core: &mut jwgpu::Core, zoom:f32)->Result<Core, jwgpu::Error>{
         let ctx = egui::Context::default();
-        ctx.set_pixels_per_point(7.5);
+        ctx.set_zoom_factor(zoom);
+        let scale_factor = jwgpu_core.window().map_or(1.0, |window| window.scale_factor() as f32);
     
         let mut fonts = egui::FontDefinitions::default();
         fonts.font_data.insert(
@@ -181,141 +701,251 @@ impl Core{
         ctx.set_fonts(fonts);
 
         let shader = jwgpu::create_shader(&jwgpu_core.device, include_str!("shader.wgsl"));
-        let view = cgmath::ortho(0.0, jwgpu_core.size.width as f32, jwgpu_core.size.height as f32, 0.0, -1.0, 1.0)
-                * cgmath::Matrix4::from_scale(scale)
-                * jwgpu::OPENGL_TO_WGPU_MATRIX;
-        let camera = jwgpu::JCamera::new(&jwgpu_core.device, view);
-        let fonttex = jwgpu::JTexture::new(&jwgpu_core.device, MAX_TEXTURE_SIZE, MAX_TEXTURE_SIZE);
-        let render_pipeline = jwgpu::create_render_pipeline(&jwgpu_core.device, &[&fonttex.bind_group_layout, &camera.bind_group_layout], &shader, &jwgpu_core.config);
-        let pipeline = jwgpu::JRenderPipeline { texture:fonttex, camera, render_pipeline};
+        let camera = jwgpu::JCamera::new(&jwgpu_core.device, Core::camera_uniform(jwgpu_core, scale_factor*zoom));
+        let texture_bind_group_layout = jwgpu::create_texture_bind_group_layout(&jwgpu_core.device);
+        let builder = jwgpu::PipelineBuilder::new(&shader, jwgpu_core.config.view_formats[0])
+            .with_label("egui_pipeline")
+            .with_bind_group_layouts(&[&texture_bind_group_layout, &camera.bind_group_layout])
+            .with_vertex::<jwgpu::Vertex>()
+            .with_blend(jwgpu::BlendMode::Premultiplied);
+        let render_pipeline = jwgpu_core.pipelines.get_or_build(&jwgpu_core.device, &builder);
+        let pipeline = jwgpu::JRenderPipeline { texture_bind_group_layout, camera, render_pipeline};
+        let vertex_buffer = jwgpu::JBuffer::new(&jwgpu_core.device, "egui_vertex_buffer", wgpu::BufferUsages::VERTEX, INITIAL_VERTEX_BUFFER_SIZE);
+        let index_buffer = jwgpu::JBuffer::new(&jwgpu_core.device, "egui_index_buffer", wgpu::BufferUsages::INDEX, INITIAL_INDEX_BUFFER_SIZE);
+
+        let clipboard:Box<dyn jclipboard::Clipboard> = match jclipboard::SystemClipboard::new(){
+            Ok(clipboard)=>Box::new(clipboard),
+            Err(err)=>{
+                log::warn!("system clipboard unavailable, copied text stays in this process: {}", err);
+                Box::<jclipboard::MemoryClipboard>::default()
+            }
+        };
 
-        Core { mouse_position: egui::pos2(0.0, 0.0), scale, sizex: 0, sizey: 0, ctx, pipeline }
+        let open_url = |open_url:&egui::OpenUrl|{
+            if let Err(err) = webbrowser::open(&open_url.url){
+                log::error!("failed to open {}: {}", open_url.url, err);
+            }
+        };
+
+        Ok(Core {
+            mouse_position: egui::pos2(0.0, 0.0),
+            modifiers: egui::Modifiers::NONE,
+            clipboard,
+            open_url: Box::new(open_url),
+            cursor_icon: None,
+            ime_allowed: false,
+            ime_cursor_rect: None,
+            ime_composing: false,
+            mutable_text_under_cursor: false,
+            scale_factor,
+            textures: HashMap::new(),
+            next_user_texture_id: 0,
+            ctx,
+            pipeline,
+            vertex_buffer,
+            index_buffer,
+            callback_resources: CallbackResources::default(),
+            draws: Vec::new(),
+            textures_to_free: Vec::new(),
+            target_size: [jwgpu_core.config.width, jwgpu_core.config.height],
+            max_texture_side: jwgpu_core.device.limits().max_texture_dimension_2d.min(MAX_TEXTURE_SIZE),
+        })
     }
 
-    fn render(
+    /// Applies `full_output` and uploads everything `paint` needs: textures, vertex and index
+    /// buffers and paint callback resources. Returns the command buffers of paint callbacks,
+    /// to be submitted before `encoder`.
+    pub fn prepare(
         &mut self, 
-        full_output:egui::FullOutput, 
-        jwgpu_core: &mut jwgpu::Core,
-    ){
-        for (_id,t) in &full_output.textures_delta.set{
-            match &t.image{
-                egui::ImageData::Font(font)=>{
-                    let dimensions = &font.size;
-                    if dimensions[0]>MAX_TEXTURE_SIZE as usize || dimensions[1]>MAX_TEXTURE_SIZE as usize{
-                        panic!("font texture size larger than max texture size");
-                    }
-                    self.sizex = dimensions[0] as u32;
-                    self.sizey = dimensions[1] as u32;
-                    let mut x = 0;
-                    let mut y = 0;
-                    for p in &font.pixels{
-                        let v = (p*255.0) as u8;
-                        let i = (x*4 + y*MAX_TEXTURE_SIZE*4) as usize;
-                        self.pipeline.texture.pixels[i] = v;
-                        self.pipeline.texture.pixels[i+1] = v;
-                        self.pipeline.texture.pixels[i+2] = v;
-                        self.pipeline.texture.pixels[i+3] = v;
-                        x+=1;
-                        if x>=self.sizex{
-                            x=0;
-                            y+=1;
-                        }
-    
-                    }
-                    
-                    self.pipeline.texture.write_texture(&jwgpu_core.queue);
-                    
-                },
-                egui::ImageData::Color(_color)=>{
-    
-                },
-            }
+        jwgpu_core: &jwgpu::Core,
+        encoder:&mut wgpu::CommandEncoder,
+        mut full_output:egui::FullOutput, 
+    ) -> Result<Vec<wgpu::CommandBuffer>, jwgpu::Error> {
+        self.handle_platform_output(jwgpu_core, std::mem::take(&mut full_output.platform_output));
+        let textures_to_free = std::mem::replace(&mut self.textures_to_free, full_output.textures_delta.free);
+        self.free_textures(&textures_to_free);
+        for (id, delta) in &full_output.textures_delta.set{
+            self.update_texture(jwgpu_core, *id, delta)?;
         }
     
         let mut vertices:Vec<jwgpu::Vertex> = Vec::new();
-        let mut indices:Vec<u16> = Vec::new();
-        let mut vertices_id = 0;
+        let mut indices:Vec<u32> = Vec::new();
+        self.draws.clear();
+        self.target_size = [jwgpu_core.config.width, jwgpu_core.config.height];
+        self.update_camera(jwgpu_core, full_output.pixels_per_point);
         let clipped_primitives = self.ctx.tessellate(full_output.shapes, full_output.pixels_per_point);
-        for cp in &clipped_primitives{
-            match &cp.primitive{
+        for cp in clipped_primitives{
+            let Some(scissor) = Core::scissor_rect(cp.clip_rect, full_output.pixels_per_point, jwgpu_core) else {
+                continue;
+            };
+            match cp.primitive{
                 egui::epaint::Primitive::Mesh(mesh)=>{
+                    if !self.textures.contains_key(&mesh.texture_id){
+                        log::warn!("skipping mesh with unknown texture {:?}", mesh.texture_id);
+                        continue;
+                    }
+                    let base_vertex = vertices.len() as i32;
                     for v in &mesh.vertices{
                         vertices.push(jwgpu::Vertex { 
                             position: [v.pos.x, v.pos.y], 
-                            tex_coords: [v.uv.x*self.sizex as f32/MAX_TEXTURE_SIZE as f32, v.uv.y*self.sizey as f32/MAX_TEXTURE_SIZE as f32],
-                            color: [
-                                (v.color[0] as f32)/256.0, 
-                                (v.color[1] as f32)/256.0, 
-                                (v.color[2] as f32)/256.0, 
-                                (v.color[3] as f32)/256.0
-                            ],
-                            viewport: [cp.clip_rect.min.x, cp.clip_rect.min.y, cp.clip_rect.max.x, cp.clip_rect.max.y],
+                            tex_coords: [v.uv.x, v.uv.y],
+                            color: v.color.to_array(),
                         });
                     }
-                    for i in &mesh.indices{
-                        indices.push(*i as u16 + vertices_id);
-                    }
+                    let start = indices.len() as u32;
+                    indices.extend_from_slice(&mesh.indices);
+                    self.draws.push(Draw::Mesh{texture_id:mesh.texture_id, indices:start..indices.len() as u32, base_vertex, scissor});
                 },
-                egui::epaint::Primitive::Callback(_callback)=>{
-    
+                egui::epaint::Primitive::Callback(callback)=>{
+                    if !callback.callback.is::<Callback>(){
+                        log::warn!("skipping paint callback that is not a jegui::Callback");
+                        continue;
+                    }
+                    let info = egui::PaintCallbackInfo{
+                        viewport:callback.rect,
+                        clip_rect:cp.clip_rect,
+                        pixels_per_point:full_output.pixels_per_point,
+                        screen_size_px:self.target_size,
+                    };
+                    self.draws.push(Draw::Callback{callback:callback.callback, info, scissor});
                 },
             }
-            vertices_id = vertices.len() as u16;
         }
         
-        let vertex_buffer = jwgpu_core.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
+        self.vertex_buffer.write(&jwgpu_core.device, &jwgpu_core.queue, bytemuck::cast_slice(&vertices));
+        self.index_buffer.write(&jwgpu_core.device, &jwgpu_core.queue, bytemuck::cast_slice(&indices));
+
+        let mut command_buffers = Vec::new();
+        for draw in &self.draws{
+            if let Draw::Callback{callback, ..} = draw{
+                if let Some(callback) = callback.downcast_ref::<Callback>(){
+                    command_buffers.extend(callback.0.prepare(&jwgpu_core.device, &jwgpu_core.queue, encoder, &mut self.callback_resources));
+                }
             }
-        );
-    
-        let index_buffer = jwgpu_core.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&indices),
-                usage: wgpu::BufferUsages::INDEX,
+        }
+        Ok(command_buffers)
+    }
+
+    /// Draws the UI last passed to `prepare` into `rpass`, on top of what it already holds.
+    /// The pass must target a `config.view_formats[0]` view of the size the UI was prepared for.
+    pub fn paint<'a>(&'a self, rpass:&mut wgpu::RenderPass<'a>){
+        let [target_width, target_height] = self.target_size;
+        // Callbacks may change any pass state, so it is set again after each of them.
+        let mut needs_reset = true;
+        for draw in &self.draws{
+            match draw{
+                Draw::Mesh{texture_id, indices, base_vertex, scissor}=>{
+                    if needs_reset{
+                        rpass.set_viewport(0.0, 0.0, target_width as f32, target_height as f32, 0.0, 1.0);
+                        rpass.set_pipeline(&self.pipeline.render_pipeline);
+                        rpass.set_bind_group(1, &self.pipeline.camera.bind_group, &[]);
+                        rpass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+                        rpass.set_index_buffer(self.index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
+                        needs_reset = false;
+                    }
+                    // A native texture may have been freed since `prepare`.
+                    let Some(texture) = self.textures.get(texture_id) else {
+                        continue;
+                    };
+                    let [x, y, width, height] = *scissor;
+                    rpass.set_scissor_rect(x, y, width, height);
+                    rpass.set_bind_group(0, texture.bind_group(), &[]);
+                    rpass.draw_indexed(indices.clone(), *base_vertex, 0..1);
+                }
+                Draw::Callback{callback, info, scissor}=>{
+                    let Some(callback) = callback.downcast_ref::<Callback>() else {
+                        continue;
+                    };
+                    let viewport = info.viewport_in_pixels();
+                    if viewport.width_px<=0 || viewport.height_px<=0{
+                        continue;
+                    }
+                    rpass.set_viewport(
+                        viewport.left_px as f32,
+                        viewport.top_px as f32,
+                        viewport.width_px as f32,
+                        viewport.height_px as f32,
+                        0.0,
+                        1.0,
+                    );
+                    let [x, y, width, height] = *scissor;
+                    rpass.set_scissor_rect(x, y, width, height);
+                    callback.0.paint(info, rpass, &self.callback_resources);
+                    needs_reset = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn every_egui_key_maps_to_and_from_winit(){
+        for &key in egui::Key::ALL{
+            let code = KEY_MAP.iter().find(|(_, mapped)| *mapped==key).map(|(code, _)| *code);
+            let symbol = SYMBOL_KEYS.iter().find(|(_, mapped)| *mapped==key).map(|(symbol, _)| *symbol);
+            assert!(code.is_some() || symbol.is_some(), "{:?} has no winit key", key);
+            if let Some(code) = code{
+                assert_eq!(Core::convert_winit_keycode_to_egui_key(code), Some(key), "{:?}", code);
+            }
+            if let Some(symbol) = symbol{
+                assert_eq!(Core::convert_text_to_egui_key(symbol), Some(key), "{:?}", symbol);
             }
-        );
-    
-        let frame = jwgpu_core.surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder =
-            jwgpu_core.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: None,
-            });
-        {
-            let mut rpass =
-                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::BLUE),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    timestamp_writes: None,
-                    occlusion_query_set: None,
-                });
-            rpass.set_pipeline(&self.pipeline.render_pipeline);
-    
-            rpass.set_bind_group(0, &self.pipeline.texture.bind_group, &[]);
-            rpass.set_bind_group(1, &self.pipeline.camera.bind_group, &[]);
-    
-            rpass.set_vertex_buffer(0, vertex_buffer.slice(0..(vertices.len()*24)as u64));
-            rpass.set_index_buffer(index_buffer.slice(0..(indices.len()*2) as u64), wgpu::IndexFormat::Uint16);
-            rpass.draw_indexed(0..indices.len() as u32, 0, 0..1);
         }
-    
-        jwgpu_core.queue.submit(Some(encoder.finish()));
-        frame.present();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn winit_keys_map_once(){
+        for (i, (code, _)) in KEY_MAP.iter().enumerate(){
+            assert!(!KEY_MAP[..i].iter().any(|(other, _)| other==code), "{:?} is mapped twice", code);
+        }
+        for (i, (symbol, _)) in SYMBOL_KEYS.iter().enumerate(){
+            assert!(!SYMBOL_KEYS[..i].iter().any(|(other, _)| other==symbol), "{:?} is mapped twice", symbol);
+        }
+    }
+
+    #[test]
+    fn egui_paints_after_the_scene(){
+        let mut jwgpu_core = jwgpu::Core::headless(&jwgpu::RunConfig::default()).unwrap();
+        let egui = Core::new(&mut jwgpu_core, 1.0).unwrap();
+        let mut graph = jgraph::RenderGraph::new(&jwgpu_core.device);
+        let frame = graph.create_texture("frame", jgraph::TextureDesc::new(4, 4, wgpu::TextureFormat::Rgba8Unorm));
+        graph.add_pass("capture", |pass| { pass.read(frame); }, |_| {});
+        graph.add_pass("scene", |pass| { pass.read(frame).write(frame); }, |_| {});
+        egui.add_paint_pass(&mut graph, frame);
+        assert_eq!(graph.order().unwrap(), [1, 2, 0]);
+    }
+
+    /// The text egui receives for `events`.
+    fn typed_text(events:Vec<jinput::Event>)->Vec<String>{
+        let mut jwgpu_core = jwgpu::Core::headless(&jwgpu::RunConfig::default()).unwrap();
+        let mut egui = Core::new(&mut jwgpu_core, 1.0).unwrap();
+        jwgpu_core.events = events;
+        egui.handle_events(&mut jwgpu_core).events.into_iter().filter_map(|event| match event{
+            egui::Event::Text(text)=>Some(text),
+            _=>None,
+        }).collect()
+    }
+
+    fn key(code:KeyCode, text:&str, state:jinput::ElementState)->jinput::Event{
+        jinput::Event::KeyboardInput{physical_key:jinput::PhysicalKey::Code(code), text:Some(text.to_owned()), state, repeat:false}
+    }
+
+    #[test]
+    fn only_printable_key_presses_type_text(){
+        use jinput::ElementState::{Pressed, Released};
+        assert_eq!(typed_text(vec![key(KeyCode::KeyZ, "z", Pressed), key(KeyCode::KeyZ, "z", Released)]), ["z"]);
+        assert_eq!(typed_text(vec![key(KeyCode::Enter, "\r", Pressed), key(KeyCode::F1, "\u{f704}", Pressed)]), [] as [&str; 0]);
+        let ctrl_z = vec![
+            jinput::Event::ModifiersChanged(jinput::ModifiersState::CONTROL),
+            key(KeyCode::KeyZ, "\u{1a}", Pressed),
+            // Some platforms report the plain letter with Ctrl held.
+            key(KeyCode::KeyZ, "z", Pressed),
+            key(KeyCode::KeyZ, "z", Released),
+        ];
+        assert_eq!(typed_text(ctrl_z), [] as [&str; 0]);
+    }
+}
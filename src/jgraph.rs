@@ -0,0 +1,448 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+/// A texture in a `RenderGraph`, valid only for the graph that created it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+/// Describes a transient texture; textures with equal descriptions are shared through a `TexturePool`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TextureDesc{
+    pub width:u32,
+    pub height:u32,
+    pub format:wgpu::TextureFormat,
+    pub usage:wgpu::TextureUsages,
+    pub sample_count:u32,
+}
+
+impl TextureDesc{
+    /// A single-sampled texture usable as render attachment and for sampling.
+    pub fn new(width:u32, height:u32, format:wgpu::TextureFormat)->Self{
+        Self{
+            width,
+            height,
+            format,
+            usage:wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            sample_count:1,
+        }
+    }
+
+    pub fn with_usage(mut self, usage:wgpu::TextureUsages)->Self{
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count:u32)->Self{
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum GraphError{
+    /// The passes read and write each other's resources in a loop.
+    Cycle{passes:Vec<String>},
+}
+
+impl std::fmt::Display for GraphError{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            GraphError::Cycle{passes}=>write!(f, "render graph has a cycle between passes {}", passes.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for GraphError{}
+
+/// Transient textures kept between frames, so a graph only allocates when the descriptions change.
+/// Textures a frame didn't use are dropped at its end.
+#[derive(Default)]
+pub struct TexturePool{
+    /// Released during the previous frame.
+    available:HashMap<TextureDesc, Vec<wgpu::Texture>>,
+    /// Released during the current frame, reusable by passes that run later.
+    released:HashMap<TextureDesc, Vec<wgpu::Texture>>,
+}
+
+impl TexturePool{
+    fn acquire(&mut self, device:&wgpu::Device, desc:&TextureDesc, label:&str)->wgpu::Texture{
+        let reused = self.released.get_mut(desc).and_then(Vec::pop)
+            .or_else(|| self.available.get_mut(desc).and_then(Vec::pop));
+        reused.unwrap_or_else(|| device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: desc.width,
+                height: desc.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: desc.sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: desc.format,
+            usage: desc.usage,
+            view_formats: &[],
+        }))
+    }
+
+    fn release(&mut self, desc:&TextureDesc, texture:wgpu::Texture){
+        self.released.entry(desc.clone()).or_default().push(texture);
+    }
+
+    fn end_frame(&mut self){
+        self.available = std::mem::take(&mut self.released);
+    }
+
+    /// Number of textures held for reuse.
+    pub fn len(&self)->usize{
+        self.available.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.len()==0
+    }
+}
+
+enum ResourceKind<'a>{
+    Transient(TextureDesc),
+    Imported(&'a wgpu::TextureView),
+}
+
+struct Resource<'a>{
+    name:String,
+    kind:ResourceKind<'a>,
+}
+
+/// Collects the resources a pass reads and writes.
+#[derive(Default)]
+pub struct PassBuilder{
+    reads:Vec<ResourceId>,
+    writes:Vec<ResourceId>,
+}
+
+impl PassBuilder{
+    pub fn read(&mut self, resource:ResourceId)->&mut Self{
+        self.reads.push(resource);
+        self
+    }
+
+    /// Also declare a read when the pass loads the previous contents.
+    pub fn write(&mut self, resource:ResourceId)->&mut Self{
+        self.writes.push(resource);
+        self
+    }
+}
+
+struct Pass<'a>{
+    name:String,
+    reads:Vec<ResourceId>,
+    writes:Vec<ResourceId>,
+    run:Box<dyn FnOnce(&mut PassContext) + 'a>,
+}
+
+impl Pass<'_>{
+    fn resources(&self)->impl Iterator<Item = ResourceId> + '_{
+        self.reads.iter().chain(&self.writes).copied()
+    }
+}
+
+/// The textures a pass declared.
+pub struct GraphResources<'r>{
+    views:HashMap<ResourceId, &'r wgpu::TextureView>,
+    textures:HashMap<ResourceId, &'r wgpu::Texture>,
+}
+
+impl<'r> GraphResources<'r>{
+    /// Panics if the pass didn't declare `resource`.
+    pub fn view(&self, resource:ResourceId)->&'r wgpu::TextureView{
+        self.views.get(&resource).unwrap_or_else(|| panic!("{:?} was not declared by this pass", resource))
+    }
+
+    /// The texture behind a transient resource; `None` for imported views.
+    pub fn texture(&self, resource:ResourceId)->Option<&'r wgpu::Texture>{
+        self.textures.get(&resource).copied()
+    }
+}
+
+pub struct PassContext<'r>{
+    pub device:&'r wgpu::Device,
+    pub queue:&'r wgpu::Queue,
+    pub encoder:&'r mut wgpu::CommandEncoder,
+    pub resources:GraphResources<'r>,
+}
+
+/// The passes of one frame. Passes declare the textures they read and write; `execute` orders
+/// them so every pass reading a texture runs after all passes writing it (writers of the same
+/// texture keep the order they were added in), allocates transient textures for the span of
+/// passes using them and submits all work at once.
+pub struct RenderGraph<'a>{
+    resources:Vec<Resource<'a>>,
+    passes:Vec<Pass<'a>>,
+    encoder:wgpu::CommandEncoder,
+    command_buffers:Vec<wgpu::CommandBuffer>,
+}
+
+impl<'a> RenderGraph<'a>{
+    pub fn new(device:&wgpu::Device)->Self{
+        let encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("render_graph_encoder"),
+        });
+        Self{resources:Vec::new(), passes:Vec::new(), encoder, command_buffers:Vec::new()}
+    }
+
+    /// A texture allocated by the graph. Its contents are undefined until a pass writes it.
+    pub fn create_texture(&mut self, name:impl Into<String>, desc:TextureDesc)->ResourceId{
+        self.add_resource(name.into(), ResourceKind::Transient(desc))
+    }
+
+    /// A texture owned outside the graph, such as the frame or a persistent shadow map.
+    pub fn import_view(&mut self, name:impl Into<String>, view:&'a wgpu::TextureView)->ResourceId{
+        self.add_resource(name.into(), ResourceKind::Imported(view))
+    }
+
+    fn add_resource(&mut self, name:String, kind:ResourceKind<'a>)->ResourceId{
+        self.resources.push(Resource{name, kind});
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Adds a pass; `setup` declares its resources and `run` records its commands.
+    pub fn add_pass(
+        &mut self,
+        name:impl Into<String>,
+        setup:impl FnOnce(&mut PassBuilder),
+        run:impl FnOnce(&mut PassContext) + 'a,
+    ){
+        let mut builder = PassBuilder::default();
+        setup(&mut builder);
+        self.passes.push(Pass{name:name.into(), reads:builder.reads, writes:builder.writes, run:Box::new(run)});
+    }
+
+    /// Encoder recorded before the first pass, for uploads that must precede it.
+    pub fn encoder(&mut self)->&mut wgpu::CommandEncoder{
+        &mut self.encoder
+    }
+
+    /// Command buffers submitted ahead of the graph's own, in the same submission.
+    pub fn add_command_buffers(&mut self, command_buffers:impl IntoIterator<Item = wgpu::CommandBuffer>){
+        self.command_buffers.extend(command_buffers);
+    }
+
+    /// Indices of the passes each pass waits for.
+    fn dependencies(&self)->Vec<BTreeSet<usize>>{
+        let mut dependencies = vec![BTreeSet::new(); self.passes.len()];
+        for resource in 0..self.resources.len(){
+            let resource = ResourceId(resource);
+            let writers:Vec<usize> = (0..self.passes.len()).filter(|&i| self.passes[i].writes.contains(&resource)).collect();
+            for pair in writers.windows(2){
+                dependencies[pair[1]].insert(pair[0]);
+            }
+            for (i, pass) in self.passes.iter().enumerate(){
+                if pass.reads.contains(&resource) && !writers.contains(&i){
+                    dependencies[i].extend(writers.iter().copied());
+                }
+            }
+        }
+        dependencies
+    }
+
+    /// Pass indices in execution order; ties keep the order passes were added in.
+    pub fn order(&self)->Result<Vec<usize>, GraphError>{
+        let mut dependencies = self.dependencies();
+        let mut ready:BTreeSet<usize> = (0..self.passes.len()).filter(|&i| dependencies[i].is_empty()).collect();
+        let mut order = Vec::with_capacity(self.passes.len());
+        while let Some(pass) = ready.pop_first(){
+            order.push(pass);
+            for (i, waiting) in dependencies.iter_mut().enumerate(){
+                if waiting.remove(&pass) && waiting.is_empty(){
+                    ready.insert(i);
+                }
+            }
+        }
+        if order.len()<self.passes.len(){
+            let passes = (0..self.passes.len())
+                .filter(|i| !order.contains(i))
+                .map(|i| self.passes[i].name.clone())
+                .collect();
+            return Err(GraphError::Cycle{passes});
+        }
+        Ok(order)
+    }
+
+    /// Runs all passes and submits them with a single `queue.submit`.
+    pub fn execute(self, device:&wgpu::Device, queue:&wgpu::Queue, pool:&mut TexturePool)->Result<(), GraphError>{
+        let order = self.order()?;
+        let mut last_use = vec![0; self.resources.len()];
+        for (step, &pass) in order.iter().enumerate(){
+            for resource in self.passes[pass].resources(){
+                last_use[resource.0] = step;
+            }
+        }
+
+        let RenderGraph{resources, passes, mut encoder, mut command_buffers} = self;
+        let mut passes:Vec<Option<Pass>> = passes.into_iter().map(Some).collect();
+        let mut allocated:Vec<Option<(wgpu::Texture, wgpu::TextureView)>> = resources.iter().map(|_| None).collect();
+        for (step, &index) in order.iter().enumerate(){
+            let Some(pass) = passes[index].take() else { continue };
+            let used:Vec<ResourceId> = pass.resources().collect();
+            for &resource in &used{
+                if let ResourceKind::Transient(desc) = &resources[resource.0].kind{
+                    if allocated[resource.0].is_none(){
+                        let texture = pool.acquire(device, desc, &resources[resource.0].name);
+                        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                        allocated[resource.0] = Some((texture, view));
+                    }
+                }
+            }
+            {
+                let mut graph_resources = GraphResources{views:HashMap::new(), textures:HashMap::new()};
+                for &resource in &used{
+                    match (&resources[resource.0].kind, &allocated[resource.0]){
+                        (ResourceKind::Imported(view), _)=>{
+                            graph_resources.views.insert(resource, view);
+                        }
+                        (ResourceKind::Transient(_), Some((texture, view)))=>{
+                            graph_resources.views.insert(resource, view);
+                            graph_resources.textures.insert(resource, texture);
+                        }
+                        (ResourceKind::Transient(_), None)=>{}
+                    }
+                }
+                let mut context = PassContext{device, queue, encoder:&mut encoder, resources:graph_resources};
+                (pass.run)(&mut context);
+            }
+            for &resource in &used{
+                if last_use[resource.0]!=step{
+                    continue;
+                }
+                if let (ResourceKind::Transient(desc), Some((texture, _))) = (&resources[resource.0].kind, allocated[resource.0].take()){
+                    pool.release(desc, texture);
+                }
+            }
+        }
+        command_buffers.push(encoder.finish());
+        queue.submit(command_buffers);
+        pool.end_frame();
+        Ok(())
+    }
+
+    /// The graph in Graphviz DOT: passes as boxes, textures as ellipses (dashed when imported),
+    /// edges from written textures' producers and to their readers.
+    pub fn to_dot(&self)->String{
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+        let order = self.order().ok();
+        for (i, pass) in self.passes.iter().enumerate(){
+            let step = order.as_ref().and_then(|order| order.iter().position(|&p| p==i));
+            let label = match step{
+                Some(step)=>format!("{}. {}", step + 1, pass.name),
+                None=>pass.name.clone(),
+            };
+            let _ = writeln!(dot, "    pass{} [shape=box, label={:?}];", i, label);
+        }
+        for (i, resource) in self.resources.iter().enumerate(){
+            let (label, style) = match &resource.kind{
+                ResourceKind::Transient(desc)=>(format!("{}\n{}x{} {:?}", resource.name, desc.width, desc.height, desc.format), "solid"),
+                ResourceKind::Imported(_)=>(resource.name.clone(), "dashed"),
+            };
+            let _ = writeln!(dot, "    res{} [shape=ellipse, style={}, label={:?}];", i, style, label);
+        }
+        for (i, pass) in self.passes.iter().enumerate(){
+            for resource in &pass.reads{
+                let _ = writeln!(dot, "    res{} -> pass{};", resource.0, i);
+            }
+            for resource in &pass.writes{
+                let _ = writeln!(dot, "    pass{} -> res{};", i, resource.0);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use std::cell::RefCell;
+    use super::*;
+    use crate::jwgpu;
+
+    fn device()->(wgpu::Device, wgpu::Queue){
+        let core = jwgpu::Core::headless(&jwgpu::RunConfig::default()).unwrap();
+        (core.device, core.queue)
+    }
+
+    fn desc()->TextureDesc{
+        TextureDesc::new(4, 4, wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    #[test]
+    fn readers_run_after_writers(){
+        let (device, _queue) = device();
+        let mut graph = RenderGraph::new(&device);
+        let color = graph.create_texture("color", desc());
+        let bloom = graph.create_texture("bloom", desc());
+        graph.add_pass("composite", |pass| { pass.read(color).read(bloom); }, |_| {});
+        graph.add_pass("bloom", |pass| { pass.read(color).write(bloom); }, |_| {});
+        graph.add_pass("scene", |pass| { pass.write(color); }, |_| {});
+        graph.add_pass("overlay", |pass| { pass.write(color); }, |_| {});
+        assert_eq!(graph.order().unwrap(), [2, 3, 1, 0]);
+    }
+
+    #[test]
+    fn independent_passes_keep_their_order(){
+        let (device, _queue) = device();
+        let mut graph = RenderGraph::new(&device);
+        let a = graph.create_texture("a", desc());
+        let b = graph.create_texture("b", desc());
+        graph.add_pass("first", |pass| { pass.write(a); }, |_| {});
+        graph.add_pass("second", |pass| { pass.write(b); }, |_| {});
+        graph.add_pass("third", |_| {}, |_| {});
+        assert_eq!(graph.order().unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn cycles_are_reported(){
+        let (device, _queue) = device();
+        let mut graph = RenderGraph::new(&device);
+        let a = graph.create_texture("a", desc());
+        let b = graph.create_texture("b", desc());
+        graph.add_pass("free", |_| {}, |_| {});
+        graph.add_pass("ping", |pass| { pass.read(a).write(b); }, |_| {});
+        graph.add_pass("pong", |pass| { pass.read(b).write(a); }, |_| {});
+        match graph.order(){
+            Err(GraphError::Cycle{passes})=>assert_eq!(passes, ["ping", "pong"]),
+            other=>panic!("expected a cycle, got {:?}", other),
+        }
+        assert!(!graph.to_dot().is_empty());
+    }
+
+    #[test]
+    fn pool_reuses_transient_textures(){
+        let (device, queue) = device();
+        let mut pool = TexturePool::default();
+        let frame = |pool:&mut TexturePool|{
+            let used = RefCell::new(Vec::new());
+            let mut graph = RenderGraph::new(&device);
+            let a = graph.create_texture("a", desc());
+            let b = graph.create_texture("b", desc());
+            let c = graph.create_texture("c", TextureDesc::new(8, 8, wgpu::TextureFormat::Rgba8Unorm));
+            let record = |id:ResourceId| {
+                let used = &used;
+                move |ctx:&mut PassContext| used.borrow_mut().push(ctx.resources.texture(id).unwrap().global_id())
+            };
+            graph.add_pass("write a", |pass| { pass.write(a); }, record(a));
+            graph.add_pass("read a", |pass| { pass.read(a); }, record(a));
+            graph.add_pass("write b", |pass| { pass.write(b); }, record(b));
+            graph.add_pass("write c", |pass| { pass.write(c); }, record(c));
+            graph.execute(&device, &queue, pool).unwrap();
+            used.into_inner()
+        };
+        let first = frame(&mut pool);
+        assert_eq!(first[0], first[1]);
+        // `b` starts after `a`'s last use and shares its description, so it takes over its texture.
+        assert_eq!(first[2], first[0]);
+        assert_ne!(first[3], first[0]);
+        assert_eq!(pool.len(), 2);
+
+        let second = frame(&mut pool);
+        assert_eq!(second, first);
+        assert_eq!(pool.len(), 2);
+    }
+}
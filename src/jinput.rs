@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use winit::event::WindowEvent;
+use winit::keyboard::KeyCode;
+
+pub use winit::event::{ElementState, Ime, MouseButton, MouseScrollDelta};
+pub use winit::keyboard::{ModifiersState, PhysicalKey};
+
+/// Pixel scroll deltas (touchpads) are converted to lines at this rate.
+const PIXELS_PER_LINE:f32 = 20.0;
+
+/// The window events the engine reacts to, in a form that can be recorded and replayed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Event{
+    Resized{width:u32, height:u32},
+    ScaleFactorChanged(f64),
+    Focused(bool),
+    /// Cursor position in physical pixels.
+    CursorMoved{x:f64, y:f64},
+    CursorEntered,
+    CursorLeft,
+    MouseInput{button:MouseButton, state:ElementState},
+    MouseWheel(MouseScrollDelta),
+    KeyboardInput{physical_key:PhysicalKey, text:Option<String>, state:ElementState, repeat:bool},
+    ModifiersChanged(ModifiersState),
+    Ime(Ime),
+}
+
+impl Event{
+    pub fn from_window_event(event:&WindowEvent)->Option<Self>{
+        Some(match event{
+            WindowEvent::Resized(size)=>Event::Resized{width:size.width, height:size.height},
+            WindowEvent::ScaleFactorChanged { scale_factor, .. }=>Event::ScaleFactorChanged(*scale_factor),
+            WindowEvent::Focused(focused)=>Event::Focused(*focused),
+            WindowEvent::CursorMoved { position, .. }=>Event::CursorMoved{x:position.x, y:position.y},
+            WindowEvent::CursorEntered { .. }=>Event::CursorEntered,
+            WindowEvent::CursorLeft { .. }=>Event::CursorLeft,
+            WindowEvent::MouseInput { state, button, .. }=>Event::MouseInput{button:*button, state:*state},
+            WindowEvent::MouseWheel { delta, .. }=>Event::MouseWheel(*delta),
+            WindowEvent::KeyboardInput { event, .. }=>Event::KeyboardInput{
+                physical_key:event.physical_key,
+                text:event.text.as_ref().map(|text| text.to_string()),
+                state:event.state,
+                repeat:event.repeat,
+            },
+            WindowEvent::ModifiersChanged(modifiers)=>Event::ModifiersChanged(modifiers.state()),
+            WindowEvent::Ime(ime)=>Event::Ime(ime.clone()),
+            _=>return None,
+        })
+    }
+}
+
+/// Keyboard and mouse state built from the events of the current frame.
+pub struct Input{
+    keys_down:HashSet<KeyCode>,
+    keys_pressed:HashSet<KeyCode>,
+    keys_released:HashSet<KeyCode>,
+    buttons_down:HashSet<MouseButton>,
+    buttons_pressed:HashSet<MouseButton>,
+    buttons_released:HashSet<MouseButton>,
+    mouse_position:Option<cgmath::Vector2<f32>>,
+    mouse_delta:cgmath::Vector2<f32>,
+    scroll_delta:cgmath::Vector2<f32>,
+    modifiers:ModifiersState,
+}
+
+impl Default for Input{
+    fn default()->Self{
+        Self{
+            keys_down:HashSet::new(),
+            keys_pressed:HashSet::new(),
+            keys_released:HashSet::new(),
+            buttons_down:HashSet::new(),
+            buttons_pressed:HashSet::new(),
+            buttons_released:HashSet::new(),
+            mouse_position:None,
+            mouse_delta:cgmath::Vector2::new(0.0, 0.0),
+            scroll_delta:cgmath::Vector2::new(0.0, 0.0),
+            modifiers:ModifiersState::empty(),
+        }
+    }
+}
+
+impl Input{
+    pub fn handle_event(&mut self, event:&Event){
+        match event{
+            Event::KeyboardInput { physical_key:PhysicalKey::Code(code), state, .. }=>match state{
+                ElementState::Pressed=>{
+                    if self.keys_down.insert(*code){
+                        self.keys_pressed.insert(*code);
+                    }
+                }
+                ElementState::Released=>{
+                    if self.keys_down.remove(code){
+                        self.keys_released.insert(*code);
+                    }
+                }
+            },
+            Event::MouseInput { state, button }=>match state{
+                ElementState::Pressed=>{
+                    if self.buttons_down.insert(*button){
+                        self.buttons_pressed.insert(*button);
+                    }
+                }
+                ElementState::Released=>{
+                    if self.buttons_down.remove(button){
+                        self.buttons_released.insert(*button);
+                    }
+                }
+            },
+            Event::CursorMoved { x, y }=>{
+                let position = cgmath::Vector2::new(*x as f32, *y as f32);
+                if let Some(last) = self.mouse_position{
+                    self.mouse_delta += position - last;
+                }
+                self.mouse_position = Some(position);
+            }
+            Event::CursorLeft=>self.mouse_position = None,
+            Event::MouseWheel(delta)=>match delta{
+                MouseScrollDelta::LineDelta(x, y)=>self.scroll_delta += cgmath::Vector2::new(*x, *y),
+                MouseScrollDelta::PixelDelta(delta)=>{
+                    self.scroll_delta += cgmath::Vector2::new(delta.x as f32, delta.y as f32)/PIXELS_PER_LINE;
+                }
+            },
+            Event::ModifiersChanged(modifiers)=>self.modifiers = *modifiers,
+            // Releases that happen while unfocused never reach us, so drop everything held.
+            Event::Focused(false)=>{
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+                self.modifiers = ModifiersState::empty();
+            }
+            _=>{}
+        }
+    }
+
+    /// Clears the per-frame state: just pressed/released sets and mouse and scroll deltas.
+    pub fn end_frame(&mut self){
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = cgmath::Vector2::new(0.0, 0.0);
+        self.scroll_delta = cgmath::Vector2::new(0.0, 0.0);
+    }
+
+    pub fn is_key_down(&self, key:KeyCode)->bool{
+        self.keys_down.contains(&key)
+    }
+
+    /// The key went down this frame.
+    pub fn is_key_pressed(&self, key:KeyCode)->bool{
+        self.keys_pressed.contains(&key)
+    }
+
+    /// The key went up this frame.
+    pub fn is_key_released(&self, key:KeyCode)->bool{
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_mouse_down(&self, button:MouseButton)->bool{
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn is_mouse_pressed(&self, button:MouseButton)->bool{
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn is_mouse_released(&self, button:MouseButton)->bool{
+        self.buttons_released.contains(&button)
+    }
+
+    /// Cursor position in physical pixels, or `None` while the cursor is outside the window.
+    pub fn mouse_position(&self)->Option<cgmath::Vector2<f32>>{
+        self.mouse_position
+    }
+
+    /// Cursor movement this frame in physical pixels.
+    pub fn mouse_delta(&self)->cgmath::Vector2<f32>{
+        self.mouse_delta
+    }
+
+    /// Scroll this frame in lines, positive y scrolling up.
+    pub fn scroll_delta(&self)->cgmath::Vector2<f32>{
+        self.scroll_delta
+    }
+
+    pub fn modifiers(&self)->ModifiersState{
+        self.modifiers
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn key(input:&mut Input, key:KeyCode, state:ElementState){
+        input.handle_event(&Event::KeyboardInput{physical_key:PhysicalKey::Code(key), text:None, state, repeat:false});
+    }
+
+    fn button(input:&mut Input, button:MouseButton, state:ElementState){
+        input.handle_event(&Event::MouseInput{button, state});
+    }
+
+    #[test]
+    fn pressed_and_released_last_one_frame(){
+        let mut input = Input::default();
+        key(&mut input, KeyCode::KeyW, ElementState::Pressed);
+        button(&mut input, MouseButton::Left, ElementState::Pressed);
+        assert!(input.is_key_pressed(KeyCode::KeyW));
+        assert!(input.is_mouse_pressed(MouseButton::Left));
+
+        input.end_frame();
+        assert!(!input.is_key_pressed(KeyCode::KeyW));
+        assert!(!input.is_mouse_pressed(MouseButton::Left));
+
+        key(&mut input, KeyCode::KeyW, ElementState::Released);
+        button(&mut input, MouseButton::Left, ElementState::Released);
+        assert!(input.is_key_released(KeyCode::KeyW));
+        assert!(input.is_mouse_released(MouseButton::Left));
+
+        input.end_frame();
+        assert!(!input.is_key_released(KeyCode::KeyW));
+        assert!(!input.is_mouse_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn held_keys_stay_down(){
+        let mut input = Input::default();
+        key(&mut input, KeyCode::KeyW, ElementState::Pressed);
+        button(&mut input, MouseButton::Right, ElementState::Pressed);
+        for _ in 0..3{
+            input.end_frame();
+            // Key repeat sends more presses, which don't count as new ones.
+            key(&mut input, KeyCode::KeyW, ElementState::Pressed);
+            assert!(input.is_key_down(KeyCode::KeyW));
+            assert!(!input.is_key_pressed(KeyCode::KeyW));
+            assert!(input.is_mouse_down(MouseButton::Right));
+        }
+        key(&mut input, KeyCode::KeyW, ElementState::Released);
+        assert!(!input.is_key_down(KeyCode::KeyW));
+        assert!(input.is_mouse_down(MouseButton::Right));
+    }
+
+    #[test]
+    fn losing_focus_releases_everything(){
+        let mut input = Input::default();
+        input.handle_event(&Event::ModifiersChanged(ModifiersState::SHIFT));
+        key(&mut input, KeyCode::KeyW, ElementState::Pressed);
+        button(&mut input, MouseButton::Left, ElementState::Pressed);
+        input.end_frame();
+
+        input.handle_event(&Event::Focused(false));
+        assert!(!input.is_key_down(KeyCode::KeyW));
+        assert!(input.is_key_released(KeyCode::KeyW));
+        assert!(!input.is_mouse_down(MouseButton::Left));
+        assert!(input.is_mouse_released(MouseButton::Left));
+        assert_eq!(input.modifiers(), ModifiersState::empty());
+
+        // The release that arrives after refocusing is not a second one.
+        input.end_frame();
+        key(&mut input, KeyCode::KeyW, ElementState::Released);
+        assert!(!input.is_key_released(KeyCode::KeyW));
+    }
+
+    #[test]
+    fn mouse_and_scroll_deltas_reset_each_frame(){
+        let mut input = Input::default();
+        input.handle_event(&Event::CursorMoved{x:10.0, y:20.0});
+        assert_eq!(input.mouse_delta(), cgmath::Vector2::new(0.0, 0.0));
+        input.handle_event(&Event::CursorMoved{x:13.0, y:16.0});
+        input.handle_event(&Event::MouseWheel(MouseScrollDelta::LineDelta(0.0, 1.0)));
+        input.handle_event(&Event::MouseWheel(MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition::new(0.0, 40.0))));
+        assert_eq!(input.mouse_delta(), cgmath::Vector2::new(3.0, -4.0));
+        assert_eq!(input.scroll_delta(), cgmath::Vector2::new(0.0, 3.0));
+
+        input.end_frame();
+        assert_eq!(input.mouse_delta(), cgmath::Vector2::new(0.0, 0.0));
+        assert_eq!(input.scroll_delta(), cgmath::Vector2::new(0.0, 0.0));
+        assert_eq!(input.mouse_position(), Some(cgmath::Vector2::new(13.0, 16.0)));
+        input.handle_event(&Event::CursorLeft);
+        assert_eq!(input.mouse_position(), None);
+    }
+}
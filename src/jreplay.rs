@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::jinput::Event;
+use crate::jtime::ManualClock;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame{
+    pub delta:Duration,
+    pub events:Vec<Event>,
+}
+
+/// The events and frame deltas of a run, one entry per frame. Stored as RON.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording{
+    pub frames:Vec<RecordedFrame>,
+}
+
+#[derive(Debug)]
+pub enum RecordingError{
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl std::fmt::Display for RecordingError{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            RecordingError::Io(err)=>write!(f, "failed to access recording: {}", err),
+            RecordingError::Parse(err)=>write!(f, "failed to parse recording: {}", err),
+            RecordingError::Serialize(err)=>write!(f, "failed to serialize recording: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError{}
+
+impl Recording{
+    pub fn from_ron(text:&str)->Result<Self, RecordingError>{
+        ron::from_str(text).map_err(RecordingError::Parse)
+    }
+
+    pub fn to_ron(&self)->Result<String, RecordingError>{
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(RecordingError::Serialize)
+    }
+
+    pub fn load(path:impl AsRef<Path>)->Result<Self, RecordingError>{
+        Self::from_ron(&std::fs::read_to_string(path).map_err(RecordingError::Io)?)
+    }
+
+    pub fn save(&self, path:impl AsRef<Path>)->Result<(), RecordingError>{
+        std::fs::write(path, self.to_ron()?).map_err(RecordingError::Io)
+    }
+}
+
+pub struct Recorder{
+    path:PathBuf,
+    recording:Recording,
+}
+
+impl Recorder{
+    /// Records frames in memory; `save` writes them to `path`.
+    pub fn new(path:impl Into<PathBuf>)->Self{
+        Self{path:path.into(), recording:Recording::default()}
+    }
+
+    pub fn record_frame(&mut self, delta:Duration, events:&[Event]){
+        self.recording.frames.push(RecordedFrame{delta, events:events.to_vec()});
+    }
+
+    pub fn recording(&self)->&Recording{
+        &self.recording
+    }
+
+    pub fn save(&self)->Result<(), RecordingError>{
+        self.recording.save(&self.path)
+    }
+}
+
+/// Plays a recording back frame by frame, driving a `ManualClock` with the recorded deltas.
+pub struct Player{
+    frames:std::vec::IntoIter<RecordedFrame>,
+    clock:ManualClock,
+}
+
+impl Player{
+    pub fn new(recording:Recording)->Self{
+        Self{frames:recording.frames.into_iter(), clock:ManualClock::default()}
+    }
+
+    /// The clock to install with `Time::set_clock` so frame deltas match the recording.
+    pub fn clock(&self)->ManualClock{
+        self.clock.clone()
+    }
+
+    /// Advances the clock by the next frame's delta and returns that frame's events.
+    pub fn next_frame(&mut self)->Option<Vec<Event>>{
+        let frame = self.frames.next()?;
+        self.clock.advance(frame.delta);
+        Some(frame.events)
+    }
+
+    pub fn remaining(&self)->usize{
+        self.frames.len()
+    }
+}
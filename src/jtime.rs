@@ -0,0 +1,243 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Longest frame delta fed into the fixed-timestep accumulator, so a long stall
+/// (breakpoint, window drag) doesn't trigger a burst of simulation steps.
+const MAX_FRAME_DELTA:Duration = Duration::from_millis(250);
+const FPS_SMOOTHING:f64 = 0.9;
+
+pub trait Clock{
+    /// Time since some fixed starting point.
+    fn now(&self)->Duration;
+}
+
+pub struct SystemClock{
+    start:Instant,
+}
+
+impl Default for SystemClock{
+    fn default()->Self{
+        Self{start:Instant::now()}
+    }
+}
+
+impl Clock for SystemClock{
+    fn now(&self)->Duration{
+        self.start.elapsed()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so keep one
+/// handle to drive the clock after giving another to `Time::set_clock`.
+#[derive(Clone, Default)]
+pub struct ManualClock{
+    now:Rc<Cell<Duration>>,
+}
+
+impl ManualClock{
+    pub fn advance(&self, delta:Duration){
+        self.now.set(self.now.get() + delta);
+    }
+
+    pub fn set(&self, now:Duration){
+        self.now.set(now);
+    }
+}
+
+impl Clock for ManualClock{
+    fn now(&self)->Duration{
+        self.now.get()
+    }
+}
+
+pub struct Time{
+    clock:Box<dyn Clock>,
+    last:Option<Duration>,
+    delta:Duration,
+    elapsed:Duration,
+    frame:u64,
+    smoothed_delta:f64,
+    fixed_timestep:Option<Duration>,
+    accumulator:Duration,
+}
+
+impl Time{
+    pub fn new(clock:Box<dyn Clock>)->Self{
+        Self{
+            clock,
+            last:None,
+            delta:Duration::ZERO,
+            elapsed:Duration::ZERO,
+            frame:0,
+            smoothed_delta:0.0,
+            fixed_timestep:None,
+            accumulator:Duration::ZERO,
+        }
+    }
+
+    /// Replaces the clock. The next delta is measured from the new clock's current time.
+    pub fn set_clock(&mut self, clock:Box<dyn Clock>){
+        if self.last.is_some(){
+            self.last = Some(clock.now());
+        }
+        self.clock = clock;
+    }
+
+    pub fn set_fixed_timestep(&mut self, fixed_timestep:Option<Duration>){
+        self.fixed_timestep = fixed_timestep;
+        self.accumulator = Duration::ZERO;
+    }
+
+    /// Starts a new frame, sampling the clock.
+    pub fn tick(&mut self){
+        let now = self.clock.now();
+        self.delta = self.last.map_or(Duration::ZERO, |last| now.saturating_sub(last));
+        self.last = Some(now);
+        self.elapsed += self.delta;
+        self.frame += 1;
+        let delta = self.delta.as_secs_f64();
+        self.smoothed_delta = if self.frame<=2 { delta } else { self.smoothed_delta*FPS_SMOOTHING + delta*(1.0-FPS_SMOOTHING) };
+        if self.fixed_timestep.is_some(){
+            self.accumulator += self.delta.min(MAX_FRAME_DELTA);
+        }
+    }
+
+    /// Takes one fixed step out of the accumulator, returning the step length, or `None`
+    /// once less than a full step remains.
+    pub fn next_fixed_step(&mut self)->Option<Duration>{
+        let step = self.fixed_timestep.filter(|step| !step.is_zero())?;
+        if self.accumulator<step{
+            return None;
+        }
+        self.accumulator -= step;
+        Some(step)
+    }
+
+    pub fn delta(&self)->Duration{
+        self.delta
+    }
+
+    pub fn delta_seconds(&self)->f32{
+        self.delta.as_secs_f32()
+    }
+
+    /// Sum of all frame deltas so far.
+    pub fn elapsed(&self)->Duration{
+        self.elapsed
+    }
+
+    /// Number of frames started, including the current one.
+    pub fn frame(&self)->u64{
+        self.frame
+    }
+
+    /// Frames per second, smoothed over recent frames.
+    pub fn fps(&self)->f32{
+        if self.smoothed_delta>0.0 { (1.0/self.smoothed_delta) as f32 } else { 0.0 }
+    }
+
+    pub fn fixed_timestep(&self)->Option<Duration>{
+        self.fixed_timestep
+    }
+
+    /// How far between the last and the next fixed step the current frame lies, in `0.0..1.0`,
+    /// for interpolating rendered state. Zero when no fixed timestep is set.
+    pub fn alpha(&self)->f32{
+        match self.fixed_timestep{
+            Some(step) if !step.is_zero()=>self.accumulator.as_secs_f32()/step.as_secs_f32(),
+            _=>0.0,
+        }
+    }
+}
+
+impl Default for Time{
+    fn default()->Self{
+        Self::new(Box::<SystemClock>::default())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn manual_time()->(Time, ManualClock){
+        let clock = ManualClock::default();
+        (Time::new(Box::new(clock.clone())), clock)
+    }
+
+    fn ms(ms:u64)->Duration{
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn delta_follows_the_clock(){
+        let (mut time, clock) = manual_time();
+        time.tick();
+        assert_eq!(time.delta(), Duration::ZERO);
+        clock.advance(ms(16));
+        time.tick();
+        assert_eq!(time.delta(), ms(16));
+        clock.advance(ms(20));
+        time.tick();
+        assert_eq!(time.delta(), ms(20));
+        assert_eq!(time.elapsed(), ms(36));
+        assert_eq!(time.frame(), 3);
+    }
+
+    #[test]
+    fn fixed_steps_drain_the_accumulator(){
+        let (mut time, clock) = manual_time();
+        time.set_fixed_timestep(Some(ms(10)));
+        time.tick();
+        assert_eq!(time.next_fixed_step(), None);
+        clock.advance(ms(25));
+        time.tick();
+        assert_eq!(time.next_fixed_step(), Some(ms(10)));
+        assert_eq!(time.next_fixed_step(), Some(ms(10)));
+        assert_eq!(time.next_fixed_step(), None);
+        assert!((time.alpha() - 0.5).abs()<1e-6);
+        clock.advance(ms(5));
+        time.tick();
+        assert_eq!(time.next_fixed_step(), Some(ms(10)));
+        assert_eq!(time.alpha(), 0.0);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_for_fixed_steps(){
+        let (mut time, clock) = manual_time();
+        time.set_fixed_timestep(Some(ms(100)));
+        time.tick();
+        clock.advance(Duration::from_secs(5));
+        time.tick();
+        assert_eq!(time.delta(), Duration::from_secs(5));
+        let steps = std::iter::from_fn(|| time.next_fixed_step()).count();
+        assert_eq!(steps, 2);
+        assert!((time.alpha() - 0.5).abs()<1e-6);
+    }
+
+    #[test]
+    fn alpha_is_zero_without_fixed_timestep(){
+        let (mut time, clock) = manual_time();
+        time.tick();
+        clock.advance(ms(7));
+        time.tick();
+        assert_eq!(time.alpha(), 0.0);
+        assert_eq!(time.next_fixed_step(), None);
+    }
+
+    #[test]
+    fn fps_is_smoothed(){
+        let (mut time, clock) = manual_time();
+        assert_eq!(time.fps(), 0.0);
+        time.tick();
+        clock.advance(ms(10));
+        time.tick();
+        assert!((time.fps() - 100.0).abs()<1e-3);
+        clock.advance(ms(20));
+        time.tick();
+        let expected = 1.0/(0.010*FPS_SMOOTHING + 0.020*(1.0 - FPS_SMOOTHING));
+        assert!((time.fps() as f64 - expected).abs()<1e-3);
+        assert!(time.fps()<100.0 && time.fps()>50.0);
+    }
+}
@@ -2,105 +2,1111 @@
 use wgpu::util::*;
 use futures::executor::block_on;
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::jgraph;
+use crate::jinput;
+use crate::jreplay;
+use crate::jtime;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct Vertex {
     pub position: [f32; 2],
     pub tex_coords: [f32; 2],
-    pub color: [f32; 4],
-    pub viewport:[f32; 4],
+    /// Premultiplied sRGB, read as normalized floats by the shader.
+    pub color: [u8; 4],
 }
 
 unsafe impl bytemuck::Pod for Vertex {}
 unsafe impl bytemuck::Zeroable for Vertex {}
 
+/// How a vertex type is laid out in a vertex buffer, for `PipelineBuilder::with_vertex`.
+pub trait VertexLayout:bytemuck::Pod{
+    const ATTRIBUTES:&'static [wgpu::VertexAttribute];
+
+    fn layout()->wgpu::VertexBufferLayout<'static>{
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: Self::ATTRIBUTES,
+        }
+    }
+}
+
+impl VertexLayout for Vertex{
+    const ATTRIBUTES:&'static [wgpu::VertexAttribute] = &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Unorm8x4];
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshVertex{
+    pub position:[f32;3],
+    pub normal:[f32;3],
+    pub tex_coords:[f32;2],
+    /// Direction of increasing u; w is the sign of the bitangent `cross(normal, tangent.xyz)`
+    /// in the direction of increasing v.
+    pub tangent:[f32;4],
+}
+
+unsafe impl bytemuck::Pod for MeshVertex {}
+unsafe impl bytemuck::Zeroable for MeshVertex {}
+
+impl VertexLayout for MeshVertex{
+    const ATTRIBUTES:&'static [wgpu::VertexAttribute] = &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Float32x4];
+}
+
+/// Format of the depth buffer `Core` keeps and `PipelineBuilder::with_depth_test` tests against.
+pub const DEPTH_FORMAT:wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Maps OpenGL clip space depth (-1 to 1) to wgpu's (0 to 1). Column-major, like `Matrix4::new`.
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
 );
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct CameraUniform{
+    /// World to clip space, `projection * view`.
+    pub view_projection:[[f32;4];4],
     pub view:[[f32;4];4],
+    pub projection:[[f32;4];4],
+    /// Clip to world space, for reconstructing positions from depth.
+    pub inverse_view_projection:[[f32;4];4],
+    /// Camera position in world space, w = 1.
+    pub position:[f32;4],
+}
+
+impl CameraUniform{
+    pub fn new(view:cgmath::Matrix4<f32>, projection:cgmath::Matrix4<f32>)->Self{
+        use cgmath::SquareMatrix;
+        let view_projection = projection*view;
+        let position = view.invert().map_or(cgmath::Vector4::unit_w(), |inverse| inverse.w);
+        Self{
+            view_projection:view_projection.into(),
+            view:view.into(),
+            projection:projection.into(),
+            inverse_view_projection:view_projection.invert().unwrap_or(cgmath::Matrix4::identity()).into(),
+            position:position.into(),
+        }
+    }
 }
 
 unsafe impl bytemuck::Pod for CameraUniform {}
 unsafe impl bytemuck::Zeroable for CameraUniform {}
 
+#[derive(Clone, Debug, PartialEq)]
+pub struct JImage{
+    pub width:u32,
+    pub height:u32,
+    pub pixels:Vec<u8>,
+}
+
+impl JImage{
+    pub fn save_png(&self, path:impl AsRef<Path>)->Result<(), png::EncodingError>{
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)
+    }
+
+    pub fn load_png(path:impl AsRef<Path>)->Result<Self, png::DecodingError>{
+        let mut decoder = png::Decoder::new(std::fs::File::open(path)?);
+        decoder.set_transformations(png::Transformations::normalize_to_color8() | png::Transformations::ALPHA);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        buf.truncate(info.buffer_size());
+        let pixels = match info.color_type{
+            png::ColorType::GrayscaleAlpha=>buf.chunks(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            _=>buf,
+        };
+        Ok(JImage{width:info.width, height:info.height, pixels})
+    }
+
+    /// Compares two images channel by channel. Returns `None` when they have the same size and
+    /// every channel is within `tolerance`, otherwise an image with the differing pixels in red.
+    pub fn diff(&self, other:&JImage, tolerance:u8)->Option<JImage>{
+        if self.width!=other.width || self.height!=other.height{
+            return Some(JImage{width:self.width, height:self.height, pixels:[255, 0, 0, 255].repeat((self.width*self.height) as usize)});
+        }
+        let mut differs = false;
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for (a, b) in self.pixels.chunks(4).zip(other.pixels.chunks(4)){
+            if a.iter().zip(b).any(|(a, b)| a.abs_diff(*b)>tolerance){
+                differs = true;
+                pixels.extend_from_slice(&[255, 0, 0, 255]);
+            }else{
+                let grey = a[0]/4 + a[1]/4 + a[2]/4;
+                pixels.extend_from_slice(&[grey, grey, grey, 255]);
+            }
+        }
+        differs.then_some(JImage{width:self.width, height:self.height, pixels})
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenError{
+    Encoding(png::EncodingError),
+    Decoding(png::DecodingError),
+    Missing(std::path::PathBuf),
+    Mismatch{golden:std::path::PathBuf, actual:std::path::PathBuf, diff:std::path::PathBuf},
+}
+
+impl std::fmt::Display for GoldenError{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            GoldenError::Encoding(err)=>write!(f, "failed to write image: {}", err),
+            GoldenError::Decoding(err)=>write!(f, "failed to read golden image: {}", err),
+            GoldenError::Missing(path)=>write!(f,
+                "golden image {} does not exist (set UPDATE_GOLDEN to create it)", path.display()),
+            GoldenError::Mismatch{golden, actual, diff}=>write!(f,
+                "frame does not match golden image {} (actual: {}, diff: {})", golden.display(), actual.display(), diff.display()),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError{}
+
+/// Checks `image` against the golden PNG at `golden`. Setting the `UPDATE_GOLDEN` environment
+/// variable writes `image` as the new golden instead; without it a missing golden is an error.
+/// On a mismatch the actual frame and a diff image are written next to the golden.
+pub fn check_golden(image:&JImage, golden:impl AsRef<Path>, tolerance:u8)->Result<(), GoldenError>{
+    check_golden_with(image, golden, tolerance, std::env::var_os("UPDATE_GOLDEN").is_some())
+}
+
+/// Like [`check_golden`], but writes `image` as the new golden when `update` is set rather than
+/// reading the environment.
+pub fn check_golden_with(image:&JImage, golden:impl AsRef<Path>, tolerance:u8, update:bool)->Result<(), GoldenError>{
+    let golden = golden.as_ref();
+    if update{
+        return image.save_png(golden).map_err(GoldenError::Encoding);
+    }
+    if !golden.exists(){
+        return Err(GoldenError::Missing(golden.to_path_buf()));
+    }
+    let expected = JImage::load_png(golden).map_err(GoldenError::Decoding)?;
+    match image.diff(&expected, tolerance){
+        Some(diff)=>{
+            let actual_path = golden.with_extension("actual.png");
+            let diff_path = golden.with_extension("diff.png");
+            image.save_png(&actual_path).map_err(GoldenError::Encoding)?;
+            diff.save_png(&diff_path).map_err(GoldenError::Encoding)?;
+            Err(GoldenError::Mismatch{golden:golden.to_path_buf(), actual:actual_path, diff:diff_path})
+        }
+        None=>Ok(()),
+    }
+}
+
+pub enum RenderTarget<'window>{
+    Surface(wgpu::Surface<'window>),
+    Offscreen(Arc<wgpu::Texture>),
+}
+
+enum FrameTexture{
+    Surface(wgpu::SurfaceTexture),
+    Offscreen(Arc<wgpu::Texture>),
+}
+
+/// The texture the current frame is drawn into.
+pub struct JFrame{
+    texture:FrameTexture,
+    /// View in the colour format pipelines target, `config.view_formats[0]`.
+    pub view:wgpu::TextureView,
+}
+
+impl JFrame{
+    fn new(texture:FrameTexture, config:&wgpu::SurfaceConfiguration)->Self{
+        let view = match &texture{
+            FrameTexture::Surface(frame)=>&frame.texture,
+            FrameTexture::Offscreen(texture)=>texture.as_ref(),
+        }.create_view(&wgpu::TextureViewDescriptor{
+            format:Some(config.view_formats[0]),
+            ..Default::default()
+        });
+        Self{texture, view}
+    }
+
+    pub fn texture(&self)->&wgpu::Texture{
+        match &self.texture{
+            FrameTexture::Surface(frame)=>&frame.texture,
+            FrameTexture::Offscreen(texture)=>texture,
+        }
+    }
+
+    fn present(self){
+        if let FrameTexture::Surface(frame) = self.texture{
+            frame.present();
+        }
+    }
+}
+
 pub struct Core<'window>{
-    pub surface:wgpu::Surface<'window>,
+    pub target:RenderTarget<'window>,
     pub size:winit::dpi::PhysicalSize<u32>,
     pub device:wgpu::Device,
     pub queue:wgpu::Queue,
     pub config:wgpu::SurfaceConfiguration,
-    pub events:Vec<winit::event::WindowEvent>,
-}
-
-pub fn run<T>(width:f64, height:f64, awake:fn(&mut Core)->T, func:fn(&mut Core, &mut T)){
-    env_logger::init();
-    let event_loop = winit::event_loop::EventLoop::new().unwrap();
-    let window = winit::window::WindowBuilder::new()
-            .with_position(winit::dpi::Position::Logical(winit::dpi::LogicalPosition{x:25.0, y:25.0}))
-            .with_inner_size(winit::dpi::Size::Logical(winit::dpi::LogicalSize{width, height}))
-            .build(&event_loop)
-            .unwrap();
-
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor{ backends:wgpu::Backends::GL, ..Default::default()});
-    let surface = instance.create_surface(&window).unwrap();
-
-    let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-        power_preference: wgpu::PowerPreference::default(), 
-        compatible_surface: Some(&surface), 
-        force_fallback_adapter: false 
-    })).unwrap();
-    let (device, queue) = block_on(adapter.request_device(
-        &wgpu::DeviceDescriptor { label: None, required_features: wgpu::Features::empty(), required_limits: wgpu::Limits::default()}, 
-        None)).unwrap();
+    pub events:Vec<jinput::Event>,
+    pub time:jtime::Time,
+    pub input:jinput::Input,
+    /// Pipelines shared by everything rendering with this core.
+    pub pipelines:PipelineCache,
+    /// Colour each frame is cleared to before `App::render`; `None` leaves clearing to the app.
+    pub clear_color:Option<wgpu::Color>,
+    window:Option<&'window winit::window::Window>,
+    frame:Option<JFrame>,
+    texture_pool:RefCell<jgraph::TexturePool>,
+    depth_view:wgpu::TextureView,
+    capture_requested:bool,
+    captured:Option<JImage>,
+    exit_requested:bool,
+    recorder:Option<jreplay::Recorder>,
+    player:Option<jreplay::Player>,
+}
+
+impl Core<'_>{
+    /// Acquires the texture to draw the next frame into. Returns `None` when the frame should be
+    /// skipped: the surface was lost or outdated (it is reconfigured here) or acquisition timed out.
+    fn acquire_frame(&self)->Result<Option<JFrame>, Error>{
+        let texture = match &self.target{
+            RenderTarget::Surface(surface)=>match surface.get_current_texture(){
+                Ok(frame)=>FrameTexture::Surface(frame),
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)=>{
+                    surface.configure(&self.device, &self.config);
+                    return Ok(None);
+                }
+                Err(wgpu::SurfaceError::Timeout)=>{
+                    log::warn!("timed out acquiring swap chain texture, skipping frame");
+                    return Ok(None);
+                }
+                Err(err)=>return Err(Error::Surface(err)),
+            },
+            RenderTarget::Offscreen(texture)=>FrameTexture::Offscreen(texture.clone()),
+        };
+        Ok(Some(JFrame::new(texture, &self.config)))
+    }
+
+    /// Acquires the frame for `App::render` and clears it to `clear_color`.
+    fn begin_render(&mut self)->Result<(), Error>{
+        self.frame = self.acquire_frame()?;
+        if let (Some(frame), Some(color)) = (&self.frame, self.clear_color){
+            let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("clear_encoder"),
+            });
+            Self::clear_pass(&mut encoder, &frame.view, color);
+            self.queue.submit(Some(encoder.finish()));
+        }
+        Ok(())
+    }
+
+    fn clear_pass(encoder:&mut wgpu::CommandEncoder, view:&wgpu::TextureView, color:wgpu::Color){
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("clear_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    /// A graph for the current frame with the frame imported as "frame" (`None` for skipped
+    /// frames). Passes drawing over the cleared frame should load it.
+    pub fn render_graph(&self)->(jgraph::RenderGraph<'_>, Option<jgraph::ResourceId>){
+        let mut graph = jgraph::RenderGraph::new(&self.device);
+        let Some(frame) = &self.frame else {
+            return (graph, None);
+        };
+        let target = graph.import_view("frame", &frame.view);
+        (graph, Some(target))
+    }
+
+    /// Executes `graph` in one submission, reusing its transient textures across frames.
+    /// The graph is logged as DOT at trace level.
+    pub fn execute_graph(&self, graph:jgraph::RenderGraph)->Result<(), Error>{
+        if log::log_enabled!(log::Level::Trace){
+            log::trace!("{}", graph.to_dot());
+        }
+        graph.execute(&self.device, &self.queue, &mut self.texture_pool.borrow_mut()).map_err(Error::Graph)
+    }
+
+    /// Depth buffer in `DEPTH_FORMAT` matching the frame size; recreated on resize.
+    pub fn depth_view(&self)->&wgpu::TextureView{
+        &self.depth_view
+    }
+
+    /// The frame being rendered, during `App::render`. `None` outside of it and for skipped frames.
+    /// It has already been cleared, so direct draws and `render_graph` passes can be mixed.
+    pub fn frame(&self)->Option<&JFrame>{
+        self.frame.as_ref()
+    }
+
+    /// Presents the frame `App::render` drew, first copying it to the CPU if a capture was requested.
+    fn end_render(&mut self)->Result<(), Error>{
+        let Some(frame) = self.frame.take() else {
+            return Ok(());
+        };
+        let captured = if self.capture_requested{
+            self.capture_requested = false;
+            self.read_texture(frame.texture()).map(Some)
+        }else{
+            Ok(None)
+        };
+        frame.present();
+        self.captured = captured?;
+        Ok(())
+    }
+
+    /// Captures the next presented frame; fetch it with `take_capture`. Does nothing (besides
+    /// logging a warning) when the surface cannot be copied from.
+    pub fn request_capture(&mut self){
+        if !self.config.usage.contains(wgpu::TextureUsages::COPY_SRC){
+            log::warn!("cannot capture frames: the surface does not support COPY_SRC (usage {:?})", self.config.usage);
+            return;
+        }
+        self.capture_requested = true;
+    }
+
+    pub fn take_capture(&mut self)->Option<JImage>{
+        self.captured.take()
+    }
+
+    /// The window being drawn to, or `None` for headless runs.
+    pub fn window(&self)->Option<&winit::window::Window>{
+        self.window
+    }
+
+    /// Ends the run loop after the current frame.
+    pub fn request_exit(&mut self){
+        self.exit_requested = true;
+    }
+
+    /// Records the events and delta of every following frame until `stop_recording`.
+    pub fn start_recording(&mut self, path:impl Into<PathBuf>){
+        self.recorder = Some(jreplay::Recorder::new(path));
+    }
+
+    /// Writes the current recording to its file, if one is in progress.
+    pub fn stop_recording(&mut self)->Result<(), Error>{
+        match self.recorder.take(){
+            Some(recorder)=>recorder.save().map_err(Error::Recording),
+            None=>Ok(()),
+        }
+    }
+
+    /// Drives the following frames from `recording` instead of live window events and time.
+    /// Live input resumes once the recording runs out.
+    pub fn replay(&mut self, recording:jreplay::Recording){
+        let player = jreplay::Player::new(recording);
+        self.time.set_clock(Box::new(player.clock()));
+        self.player = Some(player);
+    }
+
+    pub fn is_replaying(&self)->bool{
+        self.player.is_some()
+    }
+
+    fn push_window_event(&mut self, event:&winit::event::WindowEvent){
+        if let Some(event) = jinput::Event::from_window_event(event){
+            self.events.push(event);
+        }
+    }
+
+    /// Starts a frame, taking its events from the replay if one is running. Returns the size of
+    /// the last resize replayed this frame, for the caller to apply like a live one.
+    fn begin_frame(&mut self)->Option<winit::dpi::PhysicalSize<u32>>{
+        let mut resized = None;
+        if let Some(player) = &mut self.player{
+            match player.next_frame(){
+                Some(events)=>{
+                    resized = events.iter().rev().find_map(|event| match *event{
+                        jinput::Event::Resized{width, height}=>Some(winit::dpi::PhysicalSize::new(width, height)),
+                        _=>None,
+                    });
+                    self.events = events;
+                }
+                None=>{
+                    log::info!("replay finished");
+                    self.player = None;
+                    self.time.set_clock(Box::<jtime::SystemClock>::default());
+                }
+            }
+        }
+        self.time.tick();
+        for event in &self.events{
+            self.input.handle_event(event);
+        }
+        if let Some(recorder) = &mut self.recorder{
+            recorder.record_frame(self.time.delta(), &self.events);
+        }
+        resized
+    }
+
+    pub fn resize(&mut self, width:u32, height:u32){
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        self.size = winit::dpi::PhysicalSize::new(self.config.width, self.config.height);
+        match &mut self.target{
+            RenderTarget::Surface(surface)=>surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen(texture)=>*texture = Arc::new(create_offscreen_texture(&self.device, &self.config)),
+        }
+        self.depth_view = create_depth_view(&self.device, &self.config);
+    }
+
+    pub fn read_texture(&self, texture:&wgpu::Texture)->Result<JImage, Error>{
+        let format = texture.format();
+        let bgra = match format{
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb=>false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb=>true,
+            _=>return Err(Error::UnsupportedReadback{format, usage:texture.usage()}),
+        };
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC){
+            return Err(Error::UnsupportedReadback{format, usage:texture.usage()});
+        }
+        let width = texture.width();
+        let height = texture.height();
+        let unpadded_bytes_per_row = width*4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align)*align;
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_bytes_per_row*height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: None,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        block_on(receiver)
+            .unwrap_or(Err(wgpu::BufferAsyncError))
+            .map_err(Error::Readback)?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row*height) as usize);
+        {
+            let data = slice.get_mapped_range();
+            for row in data.chunks(padded_bytes_per_row as usize){
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+        buffer.unmap();
+        if bgra{
+            for pixel in pixels.chunks_exact_mut(4){
+                pixel.swap(0, 2);
+            }
+        }
+        Ok(JImage{width, height, pixels})
+    }
+}
+
+#[derive(Debug)]
+pub enum Error{
+    EventLoop(winit::error::EventLoopError),
+    Window(winit::error::OsError),
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter{backends:wgpu::Backends, power_preference:wgpu::PowerPreference},
+    MissingFeatures{adapter:String, missing:wgpu::Features},
+    UnsupportedLimits{adapter:String},
+    RequestDevice(wgpu::RequestDeviceError),
+    IncompatibleSurface{adapter:String},
+    UnsupportedPresentMode{mode:wgpu::PresentMode, supported:Vec<wgpu::PresentMode>},
+    Surface(wgpu::SurfaceError),
+    TextureTooLarge{width:u32, height:u32, max:u32},
+    Readback(wgpu::BufferAsyncError),
+    /// Only 8-bit RGBA and BGRA textures with `COPY_SRC` usage can be read back.
+    UnsupportedReadback{format:wgpu::TextureFormat, usage:wgpu::TextureUsages},
+    Recording(jreplay::RecordingError),
+    Graph(jgraph::GraphError),
+}
+
+impl std::fmt::Display for Error{
+    fn fmt(&self, f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            Error::EventLoop(err)=>write!(f, "failed to create event loop: {}", err),
+            Error::Window(err)=>write!(f, "failed to create window: {}", err),
+            Error::CreateSurface(err)=>write!(f, "failed to create surface: {}", err),
+            Error::NoAdapter{backends, power_preference}=>write!(f,
+                "no graphics adapter found for backends {:?} with power preference {:?}", backends, power_preference),
+            Error::MissingFeatures{adapter, missing}=>write!(f, "adapter {} does not support required features {:?}", adapter, missing),
+            Error::UnsupportedLimits{adapter}=>write!(f, "adapter {} does not support the required limits", adapter),
+            Error::RequestDevice(err)=>write!(f, "failed to request device: {}", err),
+            Error::IncompatibleSurface{adapter}=>write!(f, "adapter {} cannot present to the window surface", adapter),
+            Error::UnsupportedPresentMode{mode, supported}=>write!(f,
+                "present mode {:?} is not supported by the surface (supported: {:?})", mode, supported),
+            Error::Surface(err)=>write!(f, "failed to acquire swap chain texture: {}", err),
+            Error::TextureTooLarge{width, height, max}=>write!(f,
+                "texture size {}x{} is larger than the maximum texture size {}", width, height, max),
+            Error::Readback(err)=>write!(f, "failed to read back texture: {}", err),
+            Error::UnsupportedReadback{format, usage}=>write!(f,
+                "cannot read back texture with format {:?} and usage {:?}", format, usage),
+            Error::Recording(err)=>write!(f, "{}", err),
+            Error::Graph(err)=>write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error{}
+
+#[derive(Clone, Debug)]
+pub struct RunConfig{
+    pub title:String,
+    pub width:f64,
+    pub height:f64,
+    pub position:Option<winit::dpi::LogicalPosition<f64>>,
+    pub resizable:bool,
+    pub backends:wgpu::Backends,
+    pub power_preference:wgpu::PowerPreference,
+    pub required_features:wgpu::Features,
+    pub required_limits:wgpu::Limits,
+    pub present_mode:Option<wgpu::PresentMode>,
+    pub clear_color:Option<wgpu::Color>,
+    pub fixed_timestep:Option<Duration>,
+    pub record:Option<PathBuf>,
+    pub replay:Option<PathBuf>,
+}
+
+impl Default for RunConfig{
+    fn default()->Self{
+        Self{
+            title:"render_engine".to_owned(),
+            width:800.0,
+            height:600.0,
+            position:Some(winit::dpi::LogicalPosition{x:25.0, y:25.0}),
+            resizable:true,
+            backends:wgpu::Backends::GL,
+            power_preference:wgpu::PowerPreference::default(),
+            required_features:wgpu::Features::empty(),
+            required_limits:wgpu::Limits::default(),
+            present_mode:None,
+            clear_color:Some(wgpu::Color::BLUE),
+            fixed_timestep:None,
+            record:None,
+            replay:None,
+        }
+    }
+}
+
+impl RunConfig{
+    /// Window size in logical pixels; headless runs use it as the size of the offscreen target.
+    pub fn new(width:f64, height:f64)->Self{
+        Self{width, height, ..Default::default()}
+    }
+
+    pub fn with_title(mut self, title:impl Into<String>)->Self{
+        self.title = title.into();
+        self
+    }
+
+    pub fn with_position(mut self, position:Option<winit::dpi::LogicalPosition<f64>>)->Self{
+        self.position = position;
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable:bool)->Self{
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_backends(mut self, backends:wgpu::Backends)->Self{
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference:wgpu::PowerPreference)->Self{
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_features(mut self, required_features:wgpu::Features)->Self{
+        self.required_features = required_features;
+        self
+    }
+
+    pub fn with_limits(mut self, required_limits:wgpu::Limits)->Self{
+        self.required_limits = required_limits;
+        self
+    }
+
+    /// Present mode for the window surface; `None` keeps the surface's default.
+    pub fn with_present_mode(mut self, present_mode:Option<wgpu::PresentMode>)->Self{
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Colour every frame is cleared to before `App::render`; `None` if the app clears it itself.
+    pub fn with_clear_color(mut self, clear_color:Option<wgpu::Color>)->Self{
+        self.clear_color = clear_color;
+        self
+    }
+
+    /// Calls `App::fixed_update` with this step length as often as needed to keep up with real time.
+    pub fn with_fixed_timestep(mut self, fixed_timestep:Option<Duration>)->Self{
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Records every frame's events and delta to `path`, written when the run ends.
+    pub fn with_recording(mut self, path:Option<PathBuf>)->Self{
+        self.record = path;
+        self
+    }
+
+    /// Replays a recording made with `with_recording` before handing over to live input.
+    pub fn with_replay(mut self, path:Option<PathBuf>)->Self{
+        self.replay = path;
+        self
+    }
+
+    fn start_session(&self, jwgpu_core:&mut Core)->Result<(), Error>{
+        if let Some(path) = &self.replay{
+            jwgpu_core.replay(jreplay::Recording::load(path).map_err(Error::Recording)?);
+        }
+        if let Some(path) = &self.record{
+            jwgpu_core.start_recording(path.clone());
+        }
+        Ok(())
+    }
+
+    fn create_time(&self)->jtime::Time{
+        let mut time = jtime::Time::default();
+        time.set_fixed_timestep(self.fixed_timestep);
+        time
+    }
+
+    fn request_adapter(&self, instance:&wgpu::Instance, surface:Option<&wgpu::Surface>, force_fallback_adapter:bool)->Result<wgpu::Adapter, Error>{
+        block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: self.power_preference,
+            compatible_surface: surface,
+            force_fallback_adapter
+        })).ok_or(Error::NoAdapter{backends:self.backends, power_preference:self.power_preference})
+    }
+
+    fn request_device(&self, adapter:&wgpu::Adapter)->Result<(wgpu::Device, wgpu::Queue), Error>{
+        let name = adapter.get_info().name;
+        let missing = self.required_features - adapter.features();
+        if !missing.is_empty(){
+            return Err(Error::MissingFeatures{adapter:name, missing});
+        }
+        if !self.required_limits.check_limits(&adapter.limits()){
+            return Err(Error::UnsupportedLimits{adapter:name});
+        }
+        block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor { label: None, required_features: self.required_features, required_limits: self.required_limits.clone()},
+            None)).map_err(Error::RequestDevice)
+    }
+}
+
+impl Core<'static>{
+    pub fn headless(run_config:&RunConfig)->Result<Self, Error>{
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor{ backends:run_config.backends, ..Default::default()});
+        // Prefer a software adapter so headless runs behave the same on machines without a GPU.
+        let adapter = run_config.request_adapter(&instance, None, true)
+            .or_else(|_| run_config.request_adapter(&instance, None, false))?;
+        let (device, queue) = run_config.request_device(&adapter)?;
+
+        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: (run_config.width as u32).max(1),
+            height: (run_config.height as u32).max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![format],
+        };
+        let texture = Arc::new(create_offscreen_texture(&device, &config));
+        let depth_view = create_depth_view(&device, &config);
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+        Ok(Core { target:RenderTarget::Offscreen(texture), size, device, queue, config, events:Vec::new(), time:run_config.create_time(), input:jinput::Input::default(), pipelines:PipelineCache::default(), clear_color:run_config.clear_color, window:None, frame:None, texture_pool:RefCell::default(), depth_view, capture_requested:false, captured:None, exit_requested:false, recorder:None, player:None})
+    }
+}
+
+fn create_offscreen_texture(device:&wgpu::Device, config:&wgpu::SurfaceConfiguration)->wgpu::Texture{
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        label: Some("offscreen_texture"),
+        view_formats: &config.view_formats,
+    })
+}
+
+fn create_depth_view(device:&wgpu::Device, config:&wgpu::SurfaceConfiguration)->wgpu::TextureView{
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: DEPTH_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        label: Some("depth_texture"),
+        view_formats: &[],
+    }).create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Lifecycle events forwarded to `App::on_event`. Window input events are collected
+/// into `Core::events` instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppEvent{
+    Focused(bool),
+    Occluded(bool),
+    Suspended,
+    Resumed,
+}
+
+pub trait App{
+    /// Called once with the fully initialised core, before the first frame.
+    fn init(&mut self, _jwgpu_core:&mut Core)->Result<(), Error>{
+        Ok(())
+    }
+
+    /// Called every frame, after any fixed updates and before `render`.
+    fn update(&mut self, _jwgpu_core:&mut Core)->Result<(), Error>{
+        Ok(())
+    }
+
+    /// Called before `update` once per elapsed fixed step when `RunConfig::fixed_timestep` is set.
+    fn fixed_update(&mut self, _jwgpu_core:&mut Core, _step:Duration)->Result<(), Error>{
+        Ok(())
+    }
+
+    /// Called after `update` every frame to draw into `jwgpu_core.frame()`, which has been cleared
+    /// to `clear_color` and is presented when this returns.
+    fn render(&mut self, _jwgpu_core:&mut Core)->Result<(), Error>{
+        Ok(())
+    }
+
+    /// Called after the surface has been reconfigured to `size`.
+    fn resized(&mut self, _jwgpu_core:&mut Core, _size:winit::dpi::PhysicalSize<u32>){}
+
+    fn on_event(&mut self, _jwgpu_core:&mut Core, _event:AppEvent){}
+
+    /// Called once when the application is shutting down.
+    fn exit(&mut self, _jwgpu_core:&mut Core){}
+}
+
+struct FnApp<T, F>{
+    state:T,
+    update:F,
+}
+
+impl<T, F:FnMut(&mut Core, &mut T)->Result<(), Error>> App for FnApp<T, F>{
+    fn update(&mut self, jwgpu_core:&mut Core)->Result<(), Error>{
+        (self.update)(jwgpu_core, &mut self.state)
+    }
+}
+
+/// Reconfigures the target and tells the app, for live and replayed resizes alike.
+fn resize_app<A:App>(app:&mut A, jwgpu_core:&mut Core, size:winit::dpi::PhysicalSize<u32>){
+    jwgpu_core.resize(size.width, size.height);
+    app.resized(jwgpu_core, size);
+}
+
+fn run_frame<A:App>(app:&mut A, jwgpu_core:&mut Core)->Result<(), Error>{
+    if let Some(size) = jwgpu_core.begin_frame(){
+        resize_app(app, jwgpu_core, size);
+    }
+    while let Some(step) = jwgpu_core.time.next_fixed_step(){
+        app.fixed_update(jwgpu_core, step)?;
+    }
+    app.update(jwgpu_core)?;
+    jwgpu_core.begin_render()?;
+    app.render(jwgpu_core)?;
+    jwgpu_core.end_render()?;
+    jwgpu_core.events.clear();
+    jwgpu_core.input.end_frame();
+    Ok(())
+}
+
+/// Stops any recording once the app is done. An error from saving it is returned unless the run
+/// already failed, in which case it's logged and the run's error is kept.
+fn finish_recording(jwgpu_core:&mut Core, result:Result<(), Error>)->Result<(), Error>{
+    match (result, jwgpu_core.stop_recording()){
+        (Err(err), Err(stop_err))=>{
+            log::error!("failed to save the recording: {}", stop_err);
+            Err(err)
+        }
+        (result, stopped)=>result.and(stopped),
+    }
+}
+
+pub fn run<A:App>(run_config:RunConfig, init:impl FnOnce(&mut Core)->Result<A, Error>)->Result<(), Error>{
+    let _ = env_logger::try_init();
+    let event_loop = winit::event_loop::EventLoop::new().map_err(Error::EventLoop)?;
+    let mut window_builder = winit::window::WindowBuilder::new()
+            .with_title(run_config.title.as_str())
+            .with_resizable(run_config.resizable)
+            .with_inner_size(winit::dpi::Size::Logical(winit::dpi::LogicalSize{width:run_config.width, height:run_config.height}));
+    if let Some(position) = run_config.position{
+        window_builder = window_builder.with_position(winit::dpi::Position::Logical(position));
+    }
+    let window = window_builder.build(&event_loop).map_err(Error::Window)?;
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor{ backends:run_config.backends, ..Default::default()});
+    let surface = instance.create_surface(&window).map_err(Error::CreateSurface)?;
+
+    let adapter = run_config.request_adapter(&instance, Some(&surface), false)?;
+    let (device, queue) = run_config.request_device(&adapter)?;
 
     let size = window.inner_size();
-    let mut config = surface.get_default_config(&adapter, size.width, size.height).unwrap();
+    let mut config = surface.get_default_config(&adapter, size.width, size.height)
+        .ok_or_else(|| Error::IncompatibleSurface{adapter:adapter.get_info().name})?;
     let view_format = config.format.add_srgb_suffix();
     config.view_formats.push(view_format);
+    let capabilities = surface.get_capabilities(&adapter);
+    if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC){
+        config.usage |= wgpu::TextureUsages::COPY_SRC;
+    }
+    if let Some(mode) = run_config.present_mode{
+        if !capabilities.present_modes.contains(&mode){
+            return Err(Error::UnsupportedPresentMode{mode, supported:capabilities.present_modes});
+        }
+        config.present_mode = mode;
+    }
     surface.configure(&device, &config);
-    let mut jwgpu_core = Core { surface, size, device, queue, config, events:Vec::new()};
-    let mut game = awake(&mut jwgpu_core);
+    let depth_view = create_depth_view(&device, &config);
+    let mut jwgpu_core = Core { target:RenderTarget::Surface(surface), size, device, queue, config, events:Vec::new(), time:run_config.create_time(), input:jinput::Input::default(), pipelines:PipelineCache::default(), clear_color:run_config.clear_color, window:Some(&window), frame:None, texture_pool:RefCell::default(), depth_view, capture_requested:false, captured:None, exit_requested:false, recorder:None, player:None};
+    run_config.start_session(&mut jwgpu_core)?;
+    let mut app = match init(&mut jwgpu_core){
+        Ok(app)=>app,
+        Err(err)=>return finish_recording(&mut jwgpu_core, Err(err)),
+    };
+    if let Err(err) = app.init(&mut jwgpu_core){
+        app.exit(&mut jwgpu_core);
+        return finish_recording(&mut jwgpu_core, Err(err));
+    }
+    let mut result = Ok(());
     event_loop.run(|event, target| {
-        if let winit::event::Event::WindowEvent {
-            window_id: _,
-            event,
-        } = event
-        {
-            match event {
-                winit::event::WindowEvent::RedrawRequested =>{ 
-                    func(&mut jwgpu_core, &mut game);
-                    jwgpu_core.events.clear();
-                    window.request_redraw();
+        match event{
+            winit::event::Event::WindowEvent { window_id: _, event } => {
+                match event {
+                    winit::event::WindowEvent::RedrawRequested =>{ 
+                        if let Err(err) = run_frame(&mut app, &mut jwgpu_core){
+                            result = Err(err);
+                            target.exit();
+                        }
+                        if jwgpu_core.exit_requested{
+                            target.exit();
+                        }
+                        window.request_redraw();
+                    }
+                    winit::event::WindowEvent::CloseRequested => target.exit(),
+                    winit::event::WindowEvent::Resized(new_size) => {
+                        resize_app(&mut app, &mut jwgpu_core, new_size);
+                        jwgpu_core.push_window_event(&event);
+                    }
+                    winit::event::WindowEvent::Focused(focused) => {
+                        app.on_event(&mut jwgpu_core, AppEvent::Focused(focused));
+                        jwgpu_core.push_window_event(&event);
+                    }
+                    winit::event::WindowEvent::Occluded(occluded) => {
+                        app.on_event(&mut jwgpu_core, AppEvent::Occluded(occluded));
+                        jwgpu_core.push_window_event(&event);
+                    }
+                    _=>jwgpu_core.push_window_event(&event),
                 }
-                winit::event::WindowEvent::CloseRequested => target.exit(),
-                _=>jwgpu_core.events.push(event),
+            }
+            winit::event::Event::Suspended => app.on_event(&mut jwgpu_core, AppEvent::Suspended),
+            winit::event::Event::Resumed => app.on_event(&mut jwgpu_core, AppEvent::Resumed),
+            winit::event::Event::LoopExiting => {
+                app.exit(&mut jwgpu_core);
+                result = finish_recording(&mut jwgpu_core, std::mem::replace(&mut result, Ok(())));
+            }
+            _=>{}
+        }
+    }).map_err(Error::EventLoop)?;
+    result
+}
+
+/// Like `run`, but with the game state built by `awake` and advanced by the `update` closure.
+pub fn run_with<T>(
+    run_config:RunConfig,
+    awake:impl FnOnce(&mut Core)->Result<T, Error>,
+    update:impl FnMut(&mut Core, &mut T)->Result<(), Error>,
+)->Result<(), Error>{
+    run(run_config, |jwgpu_core| Ok(FnApp{state:awake(jwgpu_core)?, update}))
+}
 
+/// Runs the app for `frames` frames against an offscreen target instead of a window,
+/// returning the RGBA8 contents of the target after each frame.
+pub fn run_headless<A:App>(run_config:RunConfig, frames:u32, init:impl FnOnce(&mut Core)->Result<A, Error>)->Result<Vec<JImage>, Error>{
+    run_offscreen(run_config, frames, None, init)
+}
+
+/// Replays `recording` against an offscreen target, one frame per recorded frame,
+/// returning the RGBA8 contents of the target after each frame.
+pub fn run_replay<A:App>(run_config:RunConfig, recording:jreplay::Recording, init:impl FnOnce(&mut Core)->Result<A, Error>)->Result<Vec<JImage>, Error>{
+    let frames = recording.frames.len() as u32;
+    run_offscreen(run_config, frames, Some(recording), init)
+}
+
+fn run_offscreen<A:App>(
+    run_config:RunConfig,
+    frames:u32,
+    recording:Option<jreplay::Recording>,
+    init:impl FnOnce(&mut Core)->Result<A, Error>,
+)->Result<Vec<JImage>, Error>{
+    let _ = env_logger::try_init();
+    let mut jwgpu_core = Core::headless(&run_config)?;
+    run_config.start_session(&mut jwgpu_core)?;
+    if let Some(recording) = recording{
+        jwgpu_core.replay(recording);
+    }
+    let mut app = match init(&mut jwgpu_core){
+        Ok(app)=>app,
+        Err(err)=>return finish_recording(&mut jwgpu_core, Err(err)).map(|_| Vec::new()),
+    };
+    let mut images = Vec::with_capacity(frames as usize);
+    // Run the frames in a closure so `exit` and the recording are finished even when one fails.
+    let result = (|| {
+        app.init(&mut jwgpu_core)?;
+        for _ in 0..frames{
+            run_frame(&mut app, &mut jwgpu_core)?;
+            if let RenderTarget::Offscreen(texture) = &jwgpu_core.target{
+                images.push(jwgpu_core.read_texture(texture)?);
+            }
+            if jwgpu_core.exit_requested{
+                break;
             }
         }
-    }).unwrap();      
+        Ok(())
+    })();
+    app.exit(&mut jwgpu_core);
+    finish_recording(&mut jwgpu_core, result).map(|_| images)
+}
+
+pub fn run_headless_with<T>(
+    run_config:RunConfig,
+    frames:u32,
+    awake:impl FnOnce(&mut Core)->Result<T, Error>,
+    update:impl FnMut(&mut Core, &mut T)->Result<(), Error>,
+)->Result<Vec<JImage>, Error>{
+    run_headless(run_config, frames, |jwgpu_core| Ok(FnApp{state:awake(jwgpu_core)?, update}))
+}
+
+/// Layout of a texture bind group: a filterable 2D texture at binding 0 and its sampler at binding 1.
+pub fn create_texture_bind_group_layout(device:&wgpu::Device)->wgpu::BindGroupLayout{
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // This should match the filterable field of the
+                // corresponding Texture entry above.
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some("texture_bind_group_layout"),
+    })
+}
+
+/// Binds `view` with a clamping, linearly magnified sampler for use with `create_texture_bind_group_layout`.
+pub fn create_texture_bind_group(device:&wgpu::Device, bind_group_layout:&wgpu::BindGroupLayout, view:&wgpu::TextureView)->wgpu::BindGroup{
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+    device.create_bind_group(
+        &wgpu::BindGroupDescriptor {
+            layout: bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                }
+            ],
+            label: Some("diffuse_bind_group"),
+        }
+    )
 }
 
 pub struct JTexture{
     pub texture:wgpu::Texture,
     pub bind_group:wgpu::BindGroup,
-    pub bind_group_layout:wgpu::BindGroupLayout,
     pub size:wgpu::Extent3d,
-    pub pixels:Vec<u8>,
     pub width:u32,
     pub height:u32,
 }
 
 impl JTexture{
-    pub fn new(device:&wgpu::Device, width:u32, height:u32)->Self{
-        let pixels = vec![0; (width*height*4) as usize];
+    /// Creates an RGBA8 sRGB texture bound with a layout from `create_texture_bind_group_layout`.
+    pub fn new(device:&wgpu::Device, bind_group_layout:&wgpu::BindGroupLayout, width:u32, height:u32)->Result<Self, Error>{
+        let max = device.limits().max_texture_dimension_2d;
+        if width>max || height>max{
+            return Err(Error::TextureTooLarge{width, height, max});
+        }
         let size = wgpu::Extent3d {
             width,
             height,
@@ -114,85 +1120,85 @@ impl JTexture{
                 dimension: wgpu::TextureDimension::D2,
                 format: wgpu::TextureFormat::Rgba8UnormSrgb,
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-                label: Some("font_texture"),
+                label: Some("jtexture"),
                 view_formats: &[],
             }
         );    
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-    
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
-                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                wgpu::BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                    // This should match the filterable field of the
-                    // corresponding Texture entry above.
-                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-            label: Some("texture_bind_group_layout"),
-        });
-    
-        let bind_group = device.create_bind_group(
-            &wgpu::BindGroupDescriptor {
-                layout: &bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture_view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&sampler),
-                    }
-                ],
-                label: Some("diffuse_bind_group"),
-            }
-        );
-    
-        Self{texture, bind_group, bind_group_layout, size, pixels, width, height}
+        let bind_group = create_texture_bind_group(device, bind_group_layout, &texture_view);
+        Ok(Self{texture, bind_group, size, width, height})
+    }
+
+    /// Uploads RGBA8 `pixels` covering the whole texture.
+    pub fn write_texture(&self, queue:&wgpu::Queue, pixels:&[u8]){
+        self.write_region(queue, [0, 0], [self.width, self.height], pixels);
     }
 
-    pub fn write_texture(&self, queue:&wgpu::Queue){
+    /// Uploads RGBA8 `pixels`, `size[0]*size[1]` of them, to the rectangle starting at `origin`.
+    pub fn write_region(&self, queue:&wgpu::Queue, origin:[u32; 2], size:[u32; 2], pixels:&[u8]){
         queue.write_texture(
             wgpu::ImageCopyTexture {
                 texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x: origin[0], y: origin[1], z: 0 },
                 aspect: wgpu::TextureAspect::All,
             },
-            &self.pixels,
+            pixels,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * self.width),
-                rows_per_image: Some(self.height),
+                bytes_per_row: Some(4 * size[0]),
+                rows_per_image: Some(size[1]),
+            },
+            wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
             },
-            self.size,
         );
     }
 }
 
 
+/// A GPU buffer rewritten with `queue.write_buffer` each frame, reallocated only when
+/// the data outgrows it.
+pub struct JBuffer{
+    pub buffer:wgpu::Buffer,
+    label:&'static str,
+    usage:wgpu::BufferUsages,
+}
+
+impl JBuffer{
+    pub fn new(device:&wgpu::Device, label:&'static str, usage:wgpu::BufferUsages, capacity:wgpu::BufferAddress)->Self{
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        Self{buffer:Self::create_buffer(device, label, usage, capacity), label, usage}
+    }
+
+    fn create_buffer(device:&wgpu::Device, label:&'static str, usage:wgpu::BufferUsages, capacity:wgpu::BufferAddress)->wgpu::Buffer{
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity.max(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn capacity(&self)->wgpu::BufferAddress{
+        self.buffer.size()
+    }
+
+    /// Copies `data` to the start of the buffer, growing it to the next power of two if needed.
+    /// `data.len()` must be a multiple of `wgpu::COPY_BUFFER_ALIGNMENT`.
+    pub fn write(&mut self, device:&wgpu::Device, queue:&wgpu::Queue, data:&[u8]){
+        let size = data.len() as wgpu::BufferAddress;
+        if size>self.capacity(){
+            self.buffer = Self::create_buffer(device, self.label, self.usage, size.next_power_of_two());
+        }
+        if size>0{
+            queue.write_buffer(&self.buffer, 0, data);
+        }
+    }
+}
+
 pub struct JCamera{
     pub bind_group:wgpu::BindGroup,
     pub bind_group_layout:wgpu::BindGroupLayout,
@@ -200,8 +1206,7 @@ pub struct JCamera{
 }
 
 impl JCamera{
-    pub fn new(device:&wgpu::Device, view:cgmath::Matrix4<f32>)->Self{
-        let camera_uniform = CameraUniform{view:view.into()};
+    pub fn new(device:&wgpu::Device, camera_uniform:CameraUniform)->Self{
         let buffer = device.create_buffer_init(
             &wgpu::util::BufferInitDescriptor {
                 label: Some("Camera Buffer"),
@@ -214,7 +1219,7 @@ impl JCamera{
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -238,6 +1243,10 @@ impl JCamera{
         });
         Self{bind_group, bind_group_layout, buffer}
     }
+
+    pub fn update(&self, queue:&wgpu::Queue, camera_uniform:CameraUniform){
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[camera_uniform]));
+    }
 }
 
 
@@ -248,65 +1257,465 @@ pub fn create_shader(device:&wgpu::Device, file:&str)->wgpu::ShaderModule{
     })
 }
 
-pub fn create_render_pipeline(device:&wgpu::Device, bind_group_layouts:&[&wgpu::BindGroupLayout], shader:&wgpu::ShaderModule, config:&wgpu::SurfaceConfiguration) -> wgpu::RenderPipeline{
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts,
-        push_constant_ranges: &[],
-    });
-    let vertex_buffer_layout = wgpu::VertexBufferLayout {
-        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-        step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &[
-            wgpu::VertexAttribute {
-                offset: 0,
-                shader_location: 0,
-                format: wgpu::VertexFormat::Float32x2,
-            },
-            wgpu::VertexAttribute {
-                offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                shader_location: 1,
-                format: wgpu::VertexFormat::Float32x2,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlendMode{
+    /// Replaces the target.
+    Opaque,
+    /// Straight alpha.
+    Alpha,
+    /// Colours already multiplied by their alpha, as egui outputs.
+    Premultiplied,
+    /// Adds the colour weighted by its alpha, for glows and particles.
+    Additive,
+    /// Multiplies the target by the colour, for tinting.
+    Multiply,
+}
+
+impl BlendMode{
+    pub fn blend_state(self)->Option<wgpu::BlendState>{
+        match self{
+            BlendMode::Opaque=>None,
+            BlendMode::Alpha=>Some(wgpu::BlendState::ALPHA_BLENDING),
+            BlendMode::Premultiplied=>Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive=>Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            BlendMode::Multiply=>Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+        }
+    }
+}
+
+/// Everything about a pipeline except the shader and bind group layouts, which `PipelineKey`
+/// identifies by id.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineDesc{
+    label:Option<String>,
+    vertex_entry:String,
+    fragment_entry:Option<String>,
+    vertex_buffers:Vec<wgpu::VertexBufferLayout<'static>>,
+    targets:Vec<wgpu::ColorTargetState>,
+    primitive:wgpu::PrimitiveState,
+    depth_stencil:Option<wgpu::DepthStencilState>,
+    sample_count:u32,
+    push_constant_ranges:Vec<wgpu::PushConstantRange>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineKey{
+    shader:wgpu::Id<wgpu::ShaderModule>,
+    bind_group_layouts:Vec<wgpu::Id<wgpu::BindGroupLayout>>,
+    desc:PipelineDesc,
+}
+
+/// Builds render pipelines. Starts out with `vs_main`/`fs_main`, alpha blending into a single
+/// colour target, triangle lists without culling and no depth testing or multisampling.
+pub struct PipelineBuilder<'a>{
+    shader:&'a wgpu::ShaderModule,
+    bind_group_layouts:Vec<&'a wgpu::BindGroupLayout>,
+    desc:PipelineDesc,
+}
+
+impl<'a> PipelineBuilder<'a>{
+    pub fn new(shader:&'a wgpu::ShaderModule, color_format:wgpu::TextureFormat)->Self{
+        Self{
+            shader,
+            bind_group_layouts:Vec::new(),
+            desc:PipelineDesc{
+                label:None,
+                vertex_entry:"vs_main".to_owned(),
+                fragment_entry:Some("fs_main".to_owned()),
+                vertex_buffers:Vec::new(),
+                targets:Vec::new(),
+                primitive:wgpu::PrimitiveState::default(),
+                depth_stencil:None,
+                sample_count:1,
+                push_constant_ranges:Vec::new(),
             },
-            wgpu::VertexAttribute {
-                offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
-                shader_location: 2,
-                format: wgpu::VertexFormat::Float32x4,
+        }.with_color_target(color_format, BlendMode::Alpha)
+    }
+
+    pub fn with_label(mut self, label:&str)->Self{
+        self.desc.label = Some(label.to_owned());
+        self
+    }
+
+    pub fn with_entry_points(mut self, vertex:&str, fragment:&str)->Self{
+        self.desc.vertex_entry = vertex.to_owned();
+        self.desc.fragment_entry = Some(fragment.to_owned());
+        self
+    }
+
+    /// Drops the fragment stage and colour targets, for depth-only passes such as shadow maps.
+    pub fn without_fragment(mut self)->Self{
+        self.desc.fragment_entry = None;
+        self.desc.targets.clear();
+        self
+    }
+
+    pub fn with_bind_group_layouts(mut self, bind_group_layouts:&[&'a wgpu::BindGroupLayout])->Self{
+        self.bind_group_layouts = bind_group_layouts.to_vec();
+        self
+    }
+
+    /// Adds a vertex buffer laid out as `V`, in the next buffer slot.
+    pub fn with_vertex<V:VertexLayout>(mut self)->Self{
+        self.desc.vertex_buffers.push(V::layout());
+        self
+    }
+
+    /// Sets the blending of every colour target added so far.
+    pub fn with_blend(mut self, blend:BlendMode)->Self{
+        for target in &mut self.desc.targets{
+            target.blend = blend.blend_state();
+        }
+        self
+    }
+
+    /// Adds a colour target at the next `@location`, for rendering to several textures at once.
+    pub fn with_color_target(mut self, format:wgpu::TextureFormat, blend:BlendMode)->Self{
+        self.desc.targets.push(wgpu::ColorTargetState {
+            format,
+            blend: blend.blend_state(),
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        self
+    }
+
+    /// Strip topologies restart at `u32::MAX` indices, matching the index format `Mesh` uses.
+    pub fn with_topology(mut self, topology:wgpu::PrimitiveTopology)->Self{
+        self.desc.primitive.topology = topology;
+        self.desc.primitive.strip_index_format = topology.is_strip().then_some(wgpu::IndexFormat::Uint32);
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode:Option<wgpu::Face>)->Self{
+        self.desc.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face:wgpu::FrontFace)->Self{
+        self.desc.primitive.front_face = front_face;
+        self
+    }
+
+    /// `Line` and `Point` need `Features::POLYGON_MODE_LINE` and `POLYGON_MODE_POINT`.
+    pub fn with_polygon_mode(mut self, polygon_mode:wgpu::PolygonMode)->Self{
+        self.desc.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Tests and writes depth in `DEPTH_FORMAT`, keeping fragments closer than the stored depth.
+    pub fn with_depth_test(self)->Self{
+        self.with_depth_stencil(wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    }
+
+    pub fn with_depth_stencil(mut self, depth_stencil:wgpu::DepthStencilState)->Self{
+        self.desc.depth_stencil = Some(depth_stencil);
+        self
+    }
+
+    /// Render targets must have been created with the same sample count.
+    pub fn with_sample_count(mut self, sample_count:u32)->Self{
+        self.desc.sample_count = sample_count;
+        self
+    }
+
+    /// Needs `Features::PUSH_CONSTANTS`, and limits raised to cover `range`.
+    pub fn with_push_constants(mut self, stages:wgpu::ShaderStages, range:std::ops::Range<u32>)->Self{
+        self.desc.push_constant_ranges.push(wgpu::PushConstantRange{stages, range});
+        self
+    }
+
+    fn key(&self)->PipelineKey{
+        PipelineKey{
+            shader:self.shader.global_id(),
+            bind_group_layouts:self.bind_group_layouts.iter().map(|layout| layout.global_id()).collect(),
+            desc:self.desc.clone(),
+        }
+    }
+
+    pub fn build(&self, device:&wgpu::Device)->wgpu::RenderPipeline{
+        let desc = &self.desc;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: desc.label.as_deref(),
+            bind_group_layouts: &self.bind_group_layouts,
+            push_constant_ranges: &desc.push_constant_ranges,
+        });
+        let targets:Vec<Option<wgpu::ColorTargetState>> = desc.targets.iter().cloned().map(Some).collect();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: desc.label.as_deref(),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: self.shader,
+                entry_point: &desc.vertex_entry,
+                buffers: &desc.vertex_buffers,
             },
-            wgpu::VertexAttribute {
-                offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
-                shader_location: 3,
-                format: wgpu::VertexFormat::Float32x4,
+            fragment: desc.fragment_entry.as_deref().map(|entry_point| wgpu::FragmentState {
+                module: self.shader,
+                entry_point,
+                targets: &targets,
+            }),
+            primitive: desc.primitive,
+            depth_stencil: desc.depth_stencil.clone(),
+            multisample: wgpu::MultisampleState {
+                count: desc.sample_count,
+                ..Default::default()
             },
-        ]
-    };
+            multiview: None,
+        })
+    }
+}
 
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[vertex_buffer_layout],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format: config.view_formats[0],
-                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState::default(),
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState::default(),
-        multiview: None,
-    })
+/// Pipelines by the builder that described them, so identical pipelines are only built once.
+/// Shaders and bind group layouts are told apart by their wgpu ids.
+#[derive(Default)]
+pub struct PipelineCache{
+    pipelines:HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>,
+}
+
+impl PipelineCache{
+    pub fn get_or_build(&mut self, device:&wgpu::Device, builder:&PipelineBuilder)->Arc<wgpu::RenderPipeline>{
+        self.pipelines.entry(builder.key())
+            .or_insert_with(|| Arc::new(builder.build(device)))
+            .clone()
+    }
+
+    pub fn len(&self)->usize{
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.pipelines.is_empty()
+    }
+
+    pub fn clear(&mut self){
+        self.pipelines.clear();
+    }
+}
+
+/// An indexed triangle list.
+pub struct Mesh{
+    pub vertex_buffer:wgpu::Buffer,
+    pub index_buffer:wgpu::Buffer,
+    pub index_count:u32,
+}
+
+impl Mesh{
+    pub fn new<V:VertexLayout>(device:&wgpu::Device, label:&str, vertices:&[V], indices:&[u32])->Self{
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self{vertex_buffer, index_buffer, index_count:indices.len() as u32}
+    }
+
+    /// A cube with edges of length `size` centred on the origin. Every face has its own
+    /// vertices, so normals and tangents are flat, and counter-clockwise winding from outside.
+    pub fn cube(device:&wgpu::Device, size:f32)->Self{
+        use cgmath::Vector3;
+        let half = size/2.0;
+        // Normal and u direction of each face.
+        let faces = [
+            (Vector3::unit_x(), -Vector3::unit_z()),
+            (-Vector3::unit_x(), Vector3::unit_z()),
+            (Vector3::unit_y(), Vector3::unit_x()),
+            (-Vector3::unit_y(), Vector3::unit_x()),
+            (Vector3::unit_z(), Vector3::unit_x()),
+            (-Vector3::unit_z(), -Vector3::unit_x()),
+        ];
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for (normal, tangent) in faces{
+            // Up on the face as seen from outside, where v decreases.
+            let up = normal.cross(tangent);
+            let base = vertices.len() as u32;
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]{
+                let position = (normal + tangent*(2.0*u - 1.0) + up*(1.0 - 2.0*v))*half;
+                vertices.push(MeshVertex{
+                    position:position.into(),
+                    normal:normal.into(),
+                    tex_coords:[u, v],
+                    tangent:[tangent.x, tangent.y, tangent.z, -1.0],
+                });
+            }
+            indices.extend([0, 2, 1, 0, 3, 2].map(|i| base + i));
+        }
+        Self::new(device, "cube", &vertices, &indices)
+    }
+
+    pub fn draw<'a>(&'a self, rpass:&mut wgpu::RenderPass<'a>){
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
 }
 
 pub struct JRenderPipeline{
-    pub texture:JTexture,
+    pub texture_bind_group_layout:wgpu::BindGroupLayout,
     pub camera:JCamera,
-    pub render_pipeline:wgpu::RenderPipeline,
+    pub render_pipeline:Arc<wgpu::RenderPipeline>,
 }   
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn image(pixels:&[[u8; 4]], width:u32)->JImage{
+        JImage{width, height:pixels.len() as u32/width, pixels:pixels.concat()}
+    }
+
+    /// A fresh directory for one test's files.
+    fn temp_dir(name:&str)->std::path::PathBuf{
+        let dir = std::env::temp_dir().join(format!("jwgpu-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn diff_of_equal_images_is_none(){
+        let a = image(&[[10, 20, 30, 255], [40, 50, 60, 255]], 2);
+        assert!(a.diff(&a, 0).is_none());
+    }
+
+    #[test]
+    fn diff_respects_tolerance(){
+        let a = image(&[[10, 20, 30, 255], [40, 50, 60, 255]], 2);
+        let b = image(&[[12, 20, 30, 255], [40, 50, 57, 255]], 2);
+        assert!(a.diff(&b, 3).is_none());
+        let diff = a.diff(&b, 2).expect("images differ by more than 2");
+        assert_eq!(diff.pixels[..4], [10/4 + 20/4 + 30/4, 10/4 + 20/4 + 30/4, 10/4 + 20/4 + 30/4, 255]);
+        assert_eq!(diff.pixels[4..], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn diff_of_different_sizes_is_all_red(){
+        let a = image(&[[0, 0, 0, 255], [0, 0, 0, 255]], 2);
+        let b = image(&[[0, 0, 0, 255], [0, 0, 0, 255]], 1);
+        let diff = a.diff(&b, 255).expect("sizes differ");
+        assert_eq!((diff.width, diff.height), (2, 1));
+        assert_eq!(diff.pixels, [255, 0, 0, 255].repeat(2));
+    }
+
+    #[test]
+    fn png_round_trip(){
+        let a = image(&[[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 0], [1, 2, 3, 4]], 2);
+        let dir = temp_dir("round-trip");
+        let path = dir.join("image.png");
+        a.save_png(&path).unwrap();
+        let b = JImage::load_png(&path).unwrap();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!((b.width, b.height), (2, 2));
+        assert_eq!(b.pixels, a.pixels);
+    }
+
+    #[test]
+    fn check_golden_reports_missing_and_mismatching_images(){
+        let dir = temp_dir("golden");
+        let path = dir.join("golden.png");
+        let a = image(&[[10, 20, 30, 255]], 1);
+        assert!(matches!(check_golden_with(&a, &path, 0, false), Err(GoldenError::Missing(_))));
+        assert!(!path.exists());
+
+        check_golden_with(&a, &path, 0, true).unwrap();
+        assert!(check_golden_with(&a, &path, 0, false).is_ok());
+        let b = image(&[[20, 20, 30, 255]], 1);
+        match check_golden_with(&b, &path, 2, false){
+            Err(GoldenError::Mismatch{golden, actual, diff})=>{
+                assert_eq!(golden, path);
+                assert_eq!(JImage::load_png(&actual).unwrap().pixels, b.pixels);
+                assert_eq!(JImage::load_png(&diff).unwrap().pixels, [255, 0, 0, 255]);
+            }
+            other=>panic!("expected a mismatch, got {:?}", other),
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn failure()->Error{
+        Error::UnsupportedLimits{adapter:"test".to_owned()}
+    }
+
+    #[test]
+    fn failed_runs_still_save_the_recording(){
+        let dir = temp_dir("failed-runs");
+        let path = dir.join("init.ron");
+        let result = run_headless_with(RunConfig::default().with_recording(Some(path.clone())), 1,
+            |_| Err::<(), _>(failure()), |_, _| Ok(()));
+        assert!(matches!(result, Err(Error::UnsupportedLimits{..})));
+        assert!(path.exists());
+
+        let path = dir.join("update.ron");
+        let result = run_headless_with(RunConfig::default().with_recording(Some(path.clone())), 2,
+            |_| Ok(()), |_, _| Err(failure()));
+        assert!(matches!(result, Err(Error::UnsupportedLimits{..})));
+        assert_eq!(jreplay::Recording::load(&path).unwrap().frames.len(), 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn failing_to_save_the_recording_keeps_the_run_error(){
+        let path = std::env::temp_dir().join(format!("jwgpu-missing-{}", std::process::id())).join("run.ron");
+        let result = run_headless_with(RunConfig::default().with_recording(Some(path.clone())), 1,
+            |_| Ok(()), |_, _| Err(failure()));
+        assert!(matches!(result, Err(Error::UnsupportedLimits{..})));
+        let result = run_headless_with(RunConfig::default().with_recording(Some(path)), 1, |_| Ok(()), |_, _| Ok(()));
+        assert!(matches!(result, Err(Error::Recording(_))));
+    }
+
+    /// Clears the frame to `color` directly, without a render graph.
+    struct DirectClear{
+        color:Option<wgpu::Color>,
+    }
+
+    impl App for DirectClear{
+        fn render(&mut self, jwgpu_core:&mut Core)->Result<(), Error>{
+            if let (Some(frame), Some(color)) = (jwgpu_core.frame(), self.color){
+                let mut encoder = jwgpu_core.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                Core::clear_pass(&mut encoder, &frame.view, color);
+                jwgpu_core.queue.submit(Some(encoder.finish()));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn frames_are_cleared_before_render(){
+        let run_config = RunConfig::new(4.0, 4.0).with_clear_color(Some(wgpu::Color::RED));
+        let images = run_headless(run_config.clone(), 2, |_| Ok(DirectClear{color:None})).unwrap();
+        assert!(images.iter().all(|image| image.pixels[..4]==[255, 0, 0, 255]));
+        let images = run_headless(run_config, 2, |_| Ok(DirectClear{color:Some(wgpu::Color::GREEN)})).unwrap();
+        assert!(images.iter().all(|image| image.pixels[..4]==[0, 255, 0, 255]));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod jactions;
+pub mod jcamera;
+pub mod jclipboard;
+pub mod jegui;
+pub mod jgraph;
+pub mod jinput;
+pub mod jreplay;
+pub mod jtime;
+pub mod jwgpu;
@@ -1,40 +1,206 @@
 
-mod jegui;
-mod jwgpu;
+use cgmath::{Deg, EuclideanSpace};
+use render_engine::jcamera::{self, CameraController};
+use render_engine::{jactions, jegui, jinput, jwgpu};
+use winit::keyboard::{KeyCode, ModifiersState};
+
+const BINDINGS_FILE:&str = "bindings.ron";
+/// Initial distance of the scene camera from `MyGame::origin`.
+const CAMERA_DISTANCE:f32 = 250.0;
 
 struct MyGame{
     text:String,
     anglex:f32,
     angley:f32,
+    animate:bool,
+    /// Point the scene camera orbits.
     origin:cgmath::Vector3<f32>,
+    actions:jactions::ActionMap,
     egui:jegui::Core,
+    cube:jwgpu::Mesh,
+    camera:jcamera::Camera,
+    orbit:jcamera::OrbitController,
+    scene_camera:jwgpu::JCamera,
+    scene_pipeline:std::sync::Arc<wgpu::RenderPipeline>,
+}
+
+fn default_bindings()->jactions::ActionMap{
+    let mut actions = jactions::ActionMap::default();
+    actions.bind("screenshot", jactions::Binding::key(KeyCode::F12));
+    actions.bind("toggle_animation", jactions::Binding::key(KeyCode::KeyA).with_modifiers(ModifiersState::CONTROL | ModifiersState::SHIFT));
+    actions.bind_axis("rotate_x", jactions::AxisBinding::Buttons{
+        negative:jactions::Button::Key(KeyCode::ArrowLeft),
+        positive:jactions::Button::Key(KeyCode::ArrowRight),
+    });
+    actions.bind_axis("rotate_y", jactions::AxisBinding::Buttons{
+        negative:jactions::Button::Key(KeyCode::ArrowDown),
+        positive:jactions::Button::Key(KeyCode::ArrowUp),
+    });
+    actions
 }
 
-fn awake(jwgpu_core:&mut jwgpu::Core)->MyGame{
-    let egui = jegui::Core::new(jwgpu_core, 3.0);
-    MyGame{
-        text:"".to_owned(),
-        anglex:180.0,
-        angley:180.0,
-        origin:cgmath::Vector3 { x: 160.0, y: 160.0, z: 180.0 },
-        egui,
+impl MyGame{
+    fn new(jwgpu_core:&mut jwgpu::Core)->Result<Self, jwgpu::Error>{
+        let egui = jegui::Core::new(jwgpu_core, 3.0)?;
+        let cube = jwgpu::Mesh::cube(&jwgpu_core.device, 60.0);
+        let mut camera = jcamera::Camera::perspective(cgmath::Point3::origin(), Deg(45.0), 1.0, 1000.0);
+        camera.resize(jwgpu_core.config.width, jwgpu_core.config.height);
+        let orbit = jcamera::OrbitController::new(cgmath::Point3::origin(), CAMERA_DISTANCE);
+        let scene_camera = jwgpu::JCamera::new(&jwgpu_core.device, camera.uniform());
+        let shader = jwgpu::create_shader(&jwgpu_core.device, include_str!("mesh.wgsl"));
+        let builder = jwgpu::PipelineBuilder::new(&shader, jwgpu_core.config.view_formats[0])
+            .with_label("scene_pipeline")
+            .with_bind_group_layouts(&[&scene_camera.bind_group_layout])
+            .with_vertex::<jwgpu::MeshVertex>()
+            .with_blend(jwgpu::BlendMode::Opaque)
+            .with_cull_mode(Some(wgpu::Face::Back))
+            .with_depth_test();
+        let scene_pipeline = jwgpu_core.pipelines.get_or_build(&jwgpu_core.device, &builder);
+        let actions = match jactions::ActionMap::load(BINDINGS_FILE){
+            Ok(actions)=>actions,
+            Err(jactions::ActionMapError::Io(_))=>default_bindings(),
+            Err(err)=>{
+                log::error!("{}: {}, using default bindings", BINDINGS_FILE, err);
+                default_bindings()
+            }
+        };
+        Ok(MyGame{
+            text:"".to_owned(),
+            anglex:30.0,
+            angley:20.0,
+            animate:false,
+            origin:cgmath::Vector3 { x: 0.0, y: 0.0, z: 0.0 },
+            actions,
+            egui,
+            cube,
+            camera,
+            orbit,
+            scene_camera,
+            scene_pipeline,
+        })
     }
 }
 
-fn update(jwgpu_core:&mut jwgpu::Core, mygame:&mut MyGame){
-    mygame.egui.run(jwgpu_core, |ctx|{
-        egui::CentralPanel::default().show(&ctx, |ui| {
-            ui.heading("HelloWorld");
-            ui.text_edit_singleline(&mut mygame.text);
-            ui.add(egui::Slider::new(&mut mygame.anglex, 0.0..=360.0).text("AngleX"));
-            ui.add(egui::Slider::new(&mut mygame.angley, 0.0..=360.0).text("AngleY"));
-            ui.add(egui::Slider::new(&mut mygame.origin.x, 0.0..=360.0).text("OriginX"));
-            ui.add(egui::Slider::new(&mut mygame.origin.y, 0.0..=360.0).text("OriginY"));
-            ui.add(egui::Slider::new(&mut mygame.origin.z, 0.0..=360.0).text("OriginZ"));
+impl jwgpu::App for MyGame{
+    fn update(&mut self, jwgpu_core:&mut jwgpu::Core)->Result<(), jwgpu::Error>{
+        if let Some(image) = jwgpu_core.take_capture(){
+            if let Err(err) = image.save_png("screenshot.png"){
+                log::error!("failed to save screenshot: {}", err);
+            }
+        }
+        let input = &jwgpu_core.input;
+        // Typing into a text field or dragging a slider shouldn't also drive the scene.
+        let idle = jinput::Input::default();
+        let key_input = if self.egui.wants_keyboard_input() { &idle } else { input };
+        let camera_input = if self.egui.wants_pointer_input() { &idle } else { input };
+        if self.actions.is_pressed(key_input, "toggle_animation"){
+            self.animate = !self.animate;
+        }
+        let dt = jwgpu_core.time.delta_seconds();
+        let rotate_x = self.actions.axis(key_input, "rotate_x") + if self.animate { 0.5 } else { 0.0 };
+        self.anglex = (self.anglex + 90.0*rotate_x*dt).rem_euclid(360.0);
+        self.angley = (self.angley + 90.0*self.actions.axis(key_input, "rotate_y")*dt).clamp(-89.0, 89.0);
+        self.camera.yaw = Deg(self.anglex);
+        // AngleY raises the camera, looking down at the origin.
+        self.camera.pitch = Deg(-self.angley);
+        self.orbit.target = cgmath::Point3::from_vec(self.origin);
+        self.orbit.update(&mut self.camera, camera_input, dt);
+        self.anglex = self.camera.yaw.0;
+        self.angley = -self.camera.pitch.0;
+        if self.actions.is_pressed(key_input, "screenshot"){
+            jwgpu_core.request_capture();
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, jwgpu_core:&mut jwgpu::Core)->Result<(), jwgpu::Error>{
+        let full_output = self.egui.run_ui(jwgpu_core, |ctx|{
+            egui::Window::new("Scene").anchor(egui::Align2::RIGHT_TOP, egui::vec2(-4.0, 4.0)).default_open(false).show(ctx, |ui| {
+                ui.heading("HelloWorld");
+                ui.text_edit_singleline(&mut self.text);
+                ui.add(egui::Slider::new(&mut self.anglex, 0.0..=360.0).text("AngleX"));
+                ui.add(egui::Slider::new(&mut self.angley, -89.0..=89.0).text("AngleY"));
+                ui.checkbox(&mut self.animate, "Animate");
+                ui.add(egui::Slider::new(&mut self.origin.x, -100.0..=100.0).text("OriginX"));
+                ui.add(egui::Slider::new(&mut self.origin.y, -100.0..=100.0).text("OriginY"));
+                ui.add(egui::Slider::new(&mut self.origin.z, -100.0..=100.0).text("OriginZ"));
+            });
         });
-    });
+        self.scene_camera.update(&jwgpu_core.queue, self.camera.uniform());
+
+        let (mut graph, target) = jwgpu_core.render_graph();
+        let command_buffers = self.egui.prepare(jwgpu_core, graph.encoder(), full_output)?;
+        graph.add_command_buffers(command_buffers);
+        if let Some(target) = target{
+            let depth = graph.import_view("depth", jwgpu_core.depth_view());
+            let (pipeline, camera, cube) = (&self.scene_pipeline, &self.scene_camera, &self.cube);
+            graph.add_pass("scene", |pass| { pass.read(target).write(target).write(depth); }, move |ctx| {
+                let mut rpass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("scene_pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: ctx.resources.view(target),
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: ctx.resources.view(depth),
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: None,
+                    }),
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(0, &camera.bind_group, &[]);
+                cube.draw(&mut rpass);
+            });
+            self.egui.add_paint_pass(&mut graph, target);
+        }
+        jwgpu_core.execute_graph(graph)
+    }
+
+    fn resized(&mut self, _jwgpu_core:&mut jwgpu::Core, size:winit::dpi::PhysicalSize<u32>){
+        self.camera.resize(size.width, size.height);
+    }
 }
+
 fn main() {
-    jwgpu::run(1200.0, 800.0, awake, update);
+    let args:Vec<String> = std::env::args().collect();
+    let arg_value = |name:&str| args.iter().position(|arg| arg==name).and_then(|i| args.get(i+1));
+    let run_config = jwgpu::RunConfig::new(1200.0, 800.0)
+        .with_title("render_engine")
+        .with_recording(arg_value("--record").map(Into::into))
+        .with_replay(arg_value("--replay").map(Into::into));
+    let result = match args.iter().position(|arg| arg=="--headless"){
+        Some(i)=>{
+            let frames = args.get(i+1).and_then(|frames| frames.parse().ok()).unwrap_or(1);
+            jwgpu::run_headless(run_config.with_backends(wgpu::Backends::all()), frames, MyGame::new).map(|images|{
+                println!("rendered {} headless frames{}", images.len(), images.last().map_or(String::new(), |last| format!(" of {}x{} ({} bytes)", last.width, last.height, last.pixels.len())));
+                let Some(last) = images.last() else { return };
+                if let Some(path) = arg_value("--capture"){
+                    if let Err(err) = last.save_png(path){
+                        eprintln!("failed to save capture: {}", err);
+                        std::process::exit(1);
+                    }
+                }
+                if let Some(path) = arg_value("--golden"){
+                    if let Err(err) = jwgpu::check_golden(last, path, 2){
+                        eprintln!("{}", err);
+                        std::process::exit(1);
+                    }
+                }
+            })
+        }
+        None=>jwgpu::run(run_config, MyGame::new),
+    };
+    if let Err(err) = result{
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
-
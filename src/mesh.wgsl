@@ -0,0 +1,52 @@
+struct CameraUniform {
+    view_projection: mat4x4<f32>,
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+    @location(3) tangent: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.normal = model.normal;
+    out.tex_coords = model.tex_coords;
+    out.world_position = model.position;
+    out.position = camera.view_projection * vec4<f32>(model.position, 1.0);
+    return out;
+}
+//===================================
+
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.4, 1.0, 0.6);
+
+// Blinn-Phong shading of a checkered surface, lit from a fixed world-space direction.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let cell = vec2<i32>(floor(in.tex_coords * 4.0));
+    let checker = select(0.6, 1.0, (cell.x + cell.y) % 2 == 0);
+    let albedo = vec3<f32>(0.8, 0.5, 0.2) * checker;
+    let normal = normalize(in.normal);
+    let light = normalize(LIGHT_DIRECTION);
+    let diffuse = max(dot(normal, light), 0.0);
+    let half_vector = normalize(light + normalize(camera.position.xyz - in.world_position));
+    let specular = pow(max(dot(normal, half_vector), 0.0), 32.0) * 0.3;
+    return vec4<f32>(albedo * (0.2 + 0.8 * diffuse) + vec3<f32>(specular), 1.0);
+}
@@ -1,5 +1,9 @@
 struct CameraUniform {
+    view_projection: mat4x4<f32>,
     view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    position: vec4<f32>,
 };
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
@@ -8,15 +12,32 @@ struct VertexInput {
     @location(0) position: vec2<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) color: vec4<f32>,
-    @location(3) viewport: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) color: vec4<f32>,
-    @location(2) viewport: vec4<f32>,
-    @location(3) viewport_position:vec2<f32>,
+}
+
+// egui vertex colours are sRGB encoded and, like in egui-wgpu, are multiplied with the texel in
+// gamma space; only the result is converted to linear for the sRGB target.
+fn linear_from_gamma_rgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+fn gamma_from_linear_rgb(rgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = rgb < vec3<f32>(0.0031308);
+    let lower = rgb * vec3<f32>(12.92);
+    let higher = vec3<f32>(1.055) * pow(rgb, vec3<f32>(1.0 / 2.4)) - vec3<f32>(0.055);
+    return select(higher, lower, cutoff);
+}
+
+fn gamma_from_linear_rgba(linear_rgba: vec4<f32>) -> vec4<f32> {
+    return vec4<f32>(gamma_from_linear_rgb(linear_rgba.rgb), linear_rgba.a);
 }
 
 @vertex
@@ -26,12 +47,7 @@ fn vs_main(
     var out: VertexOutput;
     out.tex_coords = model.tex_coords;
     out.color = model.color;
-    var position = camera.view * vec4<f32>(model.position, 0.0, 1.0);
-    out.viewport_position = position.xy/position.w;
-    out.position = position;
-    out.viewport = vec4<f32>(
-        (camera.view * vec4<f32>(model.viewport.xy, 0.0, 1.0)).xy,
-        (camera.view * vec4<f32>(model.viewport.zw, 0.0, 1.0)).xy);
+    out.position = camera.view_projection * vec4<f32>(model.position, 0.0, 1.0);
     return out;
 }
 //===================================
@@ -41,20 +57,11 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
-fn between(x:f32, min:f32, max:f32) -> bool{
-    if(min<max){
-        return x>min && x<max;
-    }else{
-        return x>max && x<min;
-    }
-}
-
+// Clipping is done with scissor rects set per draw.
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var pos = in.viewport_position;
-    var vp = in.viewport;
-    if(between(pos.x, vp.x ,vp.z) && between(pos.y, vp.y, vp.w)){
-        return textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
-    }
-    discard;
-}
\ No newline at end of file
+    // Textures are sRGB, so sampling returns linear values.
+    let tex_gamma = gamma_from_linear_rgba(textureSample(t_diffuse, s_diffuse, in.tex_coords));
+    let out_color_gamma = in.color * tex_gamma;
+    return vec4<f32>(linear_from_gamma_rgb(out_color_gamma.rgb), out_color_gamma.a);
+}
@@ -0,0 +1,103 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use render_engine::jclipboard::{Clipboard, MemoryClipboard};
+use render_engine::jinput::{ElementState, Event, ModifiersState, MouseButton, PhysicalKey};
+use render_engine::jreplay::{RecordedFrame, Recording};
+use render_engine::jwgpu;
+use winit::keyboard::KeyCode;
+
+mod common;
+use common::UiApp;
+
+fn frame(events:Vec<Event>)->RecordedFrame{
+    common::frame(16, events)
+}
+
+fn key(code:KeyCode, state:ElementState)->Event{
+    Event::KeyboardInput{physical_key:PhysicalKey::Code(code), text:None, state, repeat:false}
+}
+
+/// One frame per character, each a key press producing that character as text.
+fn typed(text:&str)->Vec<RecordedFrame>{
+    text.chars().map(|c| frame(vec![Event::KeyboardInput{
+        physical_key:PhysicalKey::Code(KeyCode::KeyH),
+        text:Some(c.to_string()),
+        state:ElementState::Pressed,
+        repeat:false,
+    }])).collect()
+}
+
+/// Presses and releases `code` with Ctrl held.
+fn ctrl(code:KeyCode)->Vec<RecordedFrame>{
+    vec![
+        frame(vec![Event::ModifiersChanged(ModifiersState::CONTROL), key(code, ElementState::Pressed)]),
+        frame(vec![key(code, ElementState::Released), Event::ModifiersChanged(ModifiersState::empty())]),
+    ]
+}
+
+/// Runs `frames` after focusing the text field, returning the clipboard events and final text.
+fn run(clipboard:&MemoryClipboard, frames:Vec<RecordedFrame>)->(Vec<egui::Event>, String){
+    let mut recording = Recording{frames:vec![
+        frame(vec![]),
+        frame(vec![Event::CursorMoved{x:40.0, y:16.0}]),
+        frame(vec![Event::MouseInput{button:MouseButton::Left, state:ElementState::Pressed}]),
+        frame(vec![Event::MouseInput{button:MouseButton::Left, state:ElementState::Released}]),
+    ]};
+    recording.frames.extend(frames);
+    recording.frames.push(frame(vec![]));
+    let text = Rc::new(RefCell::new(String::new()));
+    let events = Rc::new(RefCell::new(Vec::new()));
+    // A text field filling the top of the window that logs the clipboard events egui receives.
+    let ui = |ctx:&egui::Context| {
+        events.borrow_mut().extend(ctx.input(|input| input.events.clone()).into_iter()
+            .filter(|event| matches!(event, egui::Event::Copy | egui::Event::Cut | egui::Event::Paste(_))));
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.add(egui::TextEdit::singleline(&mut *text.borrow_mut()).desired_width(f32::INFINITY));
+        });
+    };
+    jwgpu::run_replay(jwgpu::RunConfig::new(200.0, 100.0), recording, |jwgpu_core| {
+        let mut app = UiApp::new(jwgpu_core, ui)?;
+        app.egui.set_clipboard(Box::new(clipboard.clone()));
+        Ok(app)
+    }).unwrap();
+    let events = events.borrow().clone();
+    let text = text.borrow().clone();
+    (events, text)
+}
+
+#[test]
+fn ctrl_v_pastes_from_the_clipboard(){
+    let mut clipboard = MemoryClipboard::default();
+    clipboard.set("hello".to_owned());
+    let (events, text) = run(&clipboard, ctrl(KeyCode::KeyV));
+    assert_eq!(events, [egui::Event::Paste("hello".to_owned())]);
+    assert_eq!(text, "hello");
+}
+
+#[test]
+fn ctrl_v_with_an_empty_clipboard_does_nothing(){
+    let (events, text) = run(&MemoryClipboard::default(), ctrl(KeyCode::KeyV));
+    assert_eq!(events, []);
+    assert_eq!(text, "");
+}
+
+#[test]
+fn ctrl_c_copies_the_selection(){
+    let clipboard = MemoryClipboard::default();
+    let frames = [typed("hi"), ctrl(KeyCode::KeyA), ctrl(KeyCode::KeyC)].concat();
+    let (events, text) = run(&clipboard, frames);
+    assert_eq!(events, [egui::Event::Copy]);
+    assert_eq!(text, "hi");
+    assert_eq!(clipboard.clone().get().as_deref(), Some("hi"));
+}
+
+#[test]
+fn ctrl_x_cuts_the_selection(){
+    let clipboard = MemoryClipboard::default();
+    let frames = [typed("hi"), ctrl(KeyCode::KeyA), ctrl(KeyCode::KeyX)].concat();
+    let (events, text) = run(&clipboard, frames);
+    assert_eq!(events, [egui::Event::Cut]);
+    assert_eq!(text, "");
+    assert_eq!(clipboard.clone().get().as_deref(), Some("hi"));
+}
@@ -0,0 +1,40 @@
+//! Helpers shared by the integration tests and benches, which pull this in as `mod common`.
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+use render_engine::jinput::Event;
+use render_engine::jreplay::RecordedFrame;
+use render_engine::{jegui, jwgpu};
+
+/// Runs `ui` through egui every frame.
+pub struct UiApp<F>{
+    pub egui:jegui::Core,
+    pub ui:F,
+}
+
+impl<F:FnMut(&egui::Context)> UiApp<F>{
+    pub fn new(jwgpu_core:&mut jwgpu::Core, ui:F)->Result<Self, jwgpu::Error>{
+        Ok(UiApp{egui:jegui::Core::new(jwgpu_core, 1.0)?, ui})
+    }
+}
+
+impl<F:FnMut(&egui::Context)> jwgpu::App for UiApp<F>{
+    fn render(&mut self, jwgpu_core:&mut jwgpu::Core)->Result<(), jwgpu::Error>{
+        self.egui.run(jwgpu_core, &mut self.ui)
+    }
+}
+
+/// Renders `frames` frames of `ui` headlessly.
+pub fn run_ui(run_config:jwgpu::RunConfig, frames:u32, ui:impl FnMut(&egui::Context))->Vec<jwgpu::JImage>{
+    jwgpu::run_headless(run_config, frames, |jwgpu_core| UiApp::new(jwgpu_core, ui)).unwrap()
+}
+
+pub fn frame(delta_ms:u64, events:Vec<Event>)->RecordedFrame{
+    RecordedFrame{delta:Duration::from_millis(delta_ms), events}
+}
+
+/// A file name in the temp dir unique to this test process.
+pub fn temp_path(name:&str)->std::path::PathBuf{
+    std::env::temp_dir().join(format!("render-engine-{}-{}", std::process::id(), name))
+}
@@ -0,0 +1,51 @@
+//! Renders fixed UIs headlessly and compares them with the PNGs in `tests/golden`.
+//! Run with `UPDATE_GOLDEN=1` to regenerate them after an intended change.
+
+use render_engine::jwgpu;
+
+mod common;
+
+/// Channel difference allowed between software rasterizers.
+const TOLERANCE:u8 = 2;
+
+/// Renders `ui` over `clear_color` for a few frames, so sizes egui learns in one frame settle,
+/// and checks the last one against the golden image `name`.
+fn check_ui(name:&str, clear_color:wgpu::Color, ui:impl FnMut(&egui::Context)){
+    let run_config = jwgpu::RunConfig::new(240.0, 160.0).with_clear_color(Some(clear_color));
+    let images = common::run_ui(run_config, 3, ui);
+    let golden = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(format!("{}.png", name));
+    if let Err(err) = jwgpu::check_golden(images.last().unwrap(), golden, TOLERANCE){
+        panic!("{}", err);
+    }
+}
+
+#[test]
+fn widgets(){
+    check_ui("widgets", wgpu::Color::BLACK, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Heading");
+            ui.label("Anti-aliased text");
+            let _ = ui.button("Button");
+            ui.checkbox(&mut true, "Checkbox");
+            ui.add(egui::Slider::new(&mut 0.25, 0.0..=1.0).text("Slider"));
+        });
+    });
+}
+
+/// Translucent shapes and text over a light background show how vertex colours, textures and
+/// blending combine.
+#[test]
+fn translucent_shapes(){
+    check_ui("translucent_shapes", wgpu::Color{r:0.8, g:0.8, b:0.8, a:1.0}, |ctx| {
+        egui::Area::new(egui::Id::new("shapes")).show(ctx, |ui| {
+            let painter = ui.painter();
+            painter.rect_filled(egui::Rect::from_min_size(egui::pos2(10.0, 10.0), egui::vec2(100.0, 60.0)), 8.0,
+                egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128));
+            painter.circle_filled(egui::pos2(110.0, 70.0), 40.0, egui::Color32::from_rgba_unmultiplied(0, 0, 255, 96));
+            painter.text(egui::pos2(20.0, 120.0), egui::Align2::LEFT_TOP, "Coloured text",
+                egui::FontId::proportional(20.0), egui::Color32::from_rgb(0, 128, 64));
+            painter.line_segment([egui::pos2(10.0, 150.0), egui::pos2(230.0, 100.0)],
+                egui::Stroke::new(1.5, egui::Color32::from_rgba_unmultiplied(255, 255, 0, 200)));
+        });
+    });
+}
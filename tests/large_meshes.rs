@@ -0,0 +1,45 @@
+use render_engine::jwgpu;
+
+mod common;
+
+/// More vertices than a u16 index can address.
+const FILLER_VERTICES:u32 = 70_000;
+
+/// A mesh of `FILLER_VERTICES` degenerate triangles followed by a solid rect, so the rect's
+/// indices only fit in u32.
+fn mesh_with_rect(rect:egui::Rect, color:egui::Color32)->egui::Mesh{
+    let mut mesh = egui::Mesh::default();
+    for _ in 0..FILLER_VERTICES{
+        mesh.colored_vertex(rect.center(), color);
+    }
+    for i in (0..FILLER_VERTICES - 2).step_by(3){
+        mesh.add_triangle(i, i + 1, i + 2);
+    }
+    mesh.add_colored_rect(rect, color);
+    mesh
+}
+
+fn pixel(image:&jwgpu::JImage, x:u32, y:u32)->[u8; 4]{
+    let i = ((y*image.width + x)*4) as usize;
+    image.pixels[i..i + 4].try_into().unwrap()
+}
+
+#[test]
+fn meshes_past_u16_indices_draw_correctly(){
+    let run_config = jwgpu::RunConfig::new(100.0, 100.0).with_clear_color(Some(wgpu::Color::BLACK));
+    let images = common::run_ui(run_config, 2, |ctx| {
+        let painter = ctx.layer_painter(egui::LayerId::background());
+        let red = egui::Rect::from_min_max(egui::pos2(10.0, 10.0), egui::pos2(40.0, 40.0));
+        painter.add(egui::Shape::mesh(mesh_with_rect(red, egui::Color32::RED)));
+        // A second clip rect makes a second draw, starting past the first mesh's vertices.
+        let clip = egui::Rect::from_min_max(egui::pos2(50.0, 50.0), egui::pos2(100.0, 100.0));
+        let green = egui::Rect::from_min_max(egui::pos2(60.0, 60.0), egui::pos2(90.0, 90.0));
+        painter.with_clip_rect(clip).add(egui::Shape::mesh(mesh_with_rect(green, egui::Color32::GREEN)));
+    });
+    for (frame, image) in images.iter().enumerate(){
+        assert_eq!(pixel(image, 25, 25), [255, 0, 0, 255], "red rect in frame {}", frame);
+        assert_eq!(pixel(image, 75, 75), [0, 255, 0, 255], "green rect in frame {}", frame);
+        assert_eq!(pixel(image, 75, 25), [0, 0, 0, 255], "background in frame {}", frame);
+        assert_eq!(pixel(image, 25, 75), [0, 0, 0, 255], "background in frame {}", frame);
+    }
+}
@@ -0,0 +1,86 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use render_engine::jinput::{ElementState, Event, MouseButton};
+use render_engine::jreplay::Recording;
+use render_engine::{jegui, jwgpu};
+
+mod common;
+use common::{frame, temp_path};
+
+/// A button that counts its clicks next to a label showing egui's clock, so the frames depend on
+/// the replayed input, size and time.
+struct ClickApp{
+    egui:jegui::Core,
+    clicks:Rc<Cell<u32>>,
+    sizes:Rc<RefCell<Vec<(u32, u32)>>>,
+}
+
+impl jwgpu::App for ClickApp{
+    fn render(&mut self, jwgpu_core:&mut jwgpu::Core)->Result<(), jwgpu::Error>{
+        let clicks = &self.clicks;
+        self.egui.run(jwgpu_core, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ui.button(format!("clicked {}", clicks.get())).clicked(){
+                    clicks.set(clicks.get() + 1);
+                }
+                ui.label(format!("time {:.3}", ctx.input(|input| input.time)));
+            });
+        })
+    }
+
+    fn resized(&mut self, _jwgpu_core:&mut jwgpu::Core, size:winit::dpi::PhysicalSize<u32>){
+        self.sizes.borrow_mut().push((size.width, size.height));
+    }
+}
+
+fn click(state:ElementState)->Event{
+    Event::MouseInput{button:MouseButton::Left, state}
+}
+
+/// Resizes the target, then clicks the button near the top left corner.
+fn session()->Recording{
+    Recording{frames:vec![
+        frame(0, vec![]),
+        frame(16, vec![Event::Resized{width:160, height:120}]),
+        frame(16, vec![Event::CursorMoved{x:20.0, y:16.0}]),
+        frame(16, vec![click(ElementState::Pressed)]),
+        frame(16, vec![click(ElementState::Released)]),
+        frame(250, vec![]),
+        frame(16, vec![]),
+    ]}
+}
+
+fn run(run_config:jwgpu::RunConfig, recording:Recording)->(Vec<jwgpu::JImage>, u32, Vec<(u32, u32)>){
+    let sizes = Rc::new(RefCell::new(Vec::new()));
+    let clicks = Rc::new(Cell::new(0));
+    let images = jwgpu::run_replay(run_config, recording, |jwgpu_core| {
+        Ok(ClickApp{egui:jegui::Core::new(jwgpu_core, 1.0)?, clicks:clicks.clone(), sizes:sizes.clone()})
+    }).unwrap();
+    let sizes = sizes.borrow().clone();
+    (images, clicks.get(), sizes)
+}
+
+#[test]
+fn recorded_session_replays_identically(){
+    let path = temp_path("session.ron");
+    let run_config = jwgpu::RunConfig::new(200.0, 150.0);
+    let (images, clicks, sizes) = run(run_config.clone().with_recording(Some(path.clone())), session());
+    let recorded = Recording::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(recorded, session());
+    assert_eq!(clicks, 1);
+    assert_eq!(sizes, [(160, 120)]);
+    assert_eq!((images[0].width, images[0].height), (200, 150));
+    assert_eq!((images[1].width, images[1].height), (160, 120));
+
+    let (replayed, replayed_clicks, replayed_sizes) = run(run_config, recorded);
+    assert_eq!(replayed_clicks, clicks);
+    assert_eq!(replayed_sizes, sizes);
+    assert_eq!(replayed.len(), images.len());
+    for (i, (a, b)) in images.iter().zip(&replayed).enumerate(){
+        assert!(a.diff(b, 0).is_none(), "frame {} differs after replay", i);
+    }
+    // The clock label follows the replayed time, so the long frame changes the picture.
+    assert!(images[4].diff(&images[5], 0).is_some());
+}